@@ -1,11 +1,22 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
-use suds::Grid;
+use suds::{Grid, Solvers};
 
 fn solve_benchmarks(c: &mut Criterion) {
-    c.bench_function("backtracking_solve", |b| {
-        b.iter(|| black_box(Grid::empty()).backtracking_solve())
-    });
+    // A nearly solved grid, so that even the stochastic solver finishes.
+    let grid: Grid =
+        "3.5416892416892375892.75416734561928561928.34928734561153647289647289153289153647"
+            .parse()
+            .unwrap();
+
+    let solvers = Solvers::default();
+    let mut group = c.benchmark_group("solve");
+    for solver in solvers.iter() {
+        group.bench_function(solver.name(), |b| {
+            b.iter(|| solver.solve(black_box(grid.clone())))
+        });
+    }
+    group.finish();
 }
 
 criterion_group!(benches, solve_benchmarks);
@@ -1,9 +1,25 @@
-use std::io::{stdout, Write};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, stdout, BufWriter, Write},
+    ops::{Not, RangeInclusive},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use clap::{arg_enum, Clap};
-use crossterm::{queue, style::PrintStyledContent};
-use fehler::throws;
-use suds::Grid;
+use crossterm::{
+    queue,
+    style::{Color, PrintStyledContent},
+};
+use fehler::{throw, throws};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use suds::{Board, GaveUp, Generated, MultiGrid, SolveOptions, Solver, Solvers, Technique};
 
 /// Generate, solve and explore sudoku from the command line
 #[derive(Clap)]
@@ -21,27 +37,271 @@ enum Subcommand {
     Solve(Solve),
     #[clap(name = "explore")]
     Explore(Explore),
+    #[clap(name = "daily")]
+    Daily(Daily),
+    #[clap(name = "export")]
+    Export(Export),
+    #[clap(name = "import")]
+    Import(Import),
+    #[clap(name = "repair")]
+    Repair(Repair),
 }
 
 /// Generate a new sudoku puzzle
 #[derive(Clap)]
-struct Generate {}
+struct Generate {
+    /// Generate a puzzle with exactly this many givens
+    #[clap(long = "clues", conflicts_with_all = &["min-clues", "max-clues"])]
+    clues: Option<usize>,
+    /// The fewest givens the puzzle may have, defaulting to 22 on a 9x9 grid
+    #[clap(long = "min-clues")]
+    min_clues: Option<usize>,
+    /// The most givens the puzzle may have, defaulting to 30 on a 9x9 grid
+    #[clap(long = "max-clues")]
+    max_clues: Option<usize>,
+    /// Generate a puzzle that needs this technique, and nothing harder
+    #[clap(long = "requires", conflicts_with_all = &["clues", "min-clues", "max-clues"])]
+    requires: Option<Technique>,
+    /// How many distinct puzzles to generate
+    #[clap(short = "n", long = "count", default_value = "1")]
+    count: usize,
+    /// Write the puzzles to this file, one per line
+    #[clap(short = "o", long = "output")]
+    output: Option<String>,
+    /// How many threads to generate on, defaulting to one per CPU
+    #[clap(short = "j", long = "jobs")]
+    jobs: Option<usize>,
+    /// Add columns for the clue count and the hardest technique needed
+    #[clap(long = "rate")]
+    rate: bool,
+    /// The number of values, and spaces along each side: 4, 6, 8, 9, 16 or 25
+    #[clap(long = "size", default_value = "9")]
+    size: usize,
+    #[clap(flatten)]
+    variant: Variant,
+    /// Generate a killer sudoku, with cages and their sums instead of givens
+    #[clap(long = "killer", conflicts_with_all = &["clues", "min-clues", "max-clues", "requires"])]
+    killer: bool,
+    /// Generate a greater-than sudoku, with markers between neighbouring spaces in each region
+    /// and few or no givens
+    #[clap(
+        long = "greater-than",
+        conflicts_with_all = &["clues", "min-clues", "max-clues", "requires", "killer"]
+    )]
+    greater_than: bool,
+}
 
-arg_enum! {
-    #[derive(Debug)]
-    enum Strategy {
-        Backtracking,
-        Stochastic
+#[derive(Clone)]
+enum Target {
+    Clues(RangeInclusive<usize>),
+    Technique(Technique),
+    Killer,
+    GreaterThan,
+}
+
+impl Target {
+    /// Generate a puzzle with the same rules as `template`.
+    fn generate<const BW: usize, const BH: usize, const N: usize, R: Rng + ?Sized>(
+        &self,
+        template: &Board<BW, BH, N>,
+        rng: &mut R,
+    ) -> Result<Generated<BW, BH, N>, GaveUp> {
+        match self {
+            Self::Clues(clues) => template.generate_like(clues.clone(), rng),
+            Self::Technique(technique) => {
+                template.generate_graded_like(*technique..=*technique, rng)
+            }
+            Self::Killer => template.generate_killer_like(rng),
+            Self::GreaterThan => Ok(template.generate_greater_than_like(rng)),
+        }
     }
 }
 
+/// Generate `count` distinct puzzles on `jobs` threads, writing each one out
+/// as a line as soon as it arrives.
+#[throws(Box<dyn std::error::Error>)]
+fn write_batch<const BW: usize, const BH: usize, const N: usize>(
+    target: Target,
+    template: Board<BW, BH, N>,
+    count: usize,
+    jobs: usize,
+    rate: bool,
+    out: &mut dyn Write,
+) {
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..jobs {
+        let target = target.clone();
+        let sender = sender.clone();
+        let template = template.clone();
+        thread::spawn(move || {
+            let mut rng = rand::thread_rng();
+            loop {
+                let generated = target.generate(&template, &mut rng).map(|generated| {
+                    let grade = generated.puzzle.grade();
+                    (generated.puzzle, grade)
+                });
+                // The receiver hangs up once it has enough puzzles, or on
+                // the first that couldn't be found.
+                if sender.send(generated).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    let mut seen = HashSet::new();
+    while seen.len() < count {
+        let (puzzle, grade) = receiver
+            .recv()
+            .expect("generator threads never stop early")?;
+        let line = puzzle.to_line();
+        if seen.insert(line.clone()).not() {
+            continue;
+        }
+        if rate {
+            let grade = grade.map_or("guess".to_string(), |t| t.to_string());
+            writeln!(out, "{} {} {}", line, puzzle.clues(), grade)?;
+        } else {
+            writeln!(out, "{}", line)?;
+        }
+    }
+    out.flush()?;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Solved,
+    Multiple,
+    Unsolvable,
+    GaveUp,
+}
+
+impl Outcome {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Solved => "solved",
+            Self::Multiple => "multiple",
+            Self::Unsolvable => "unsolvable",
+            Self::GaveUp => "gave-up",
+        }
+    }
+}
+
+/// Solve `puzzles` on `jobs` threads, writing a line for each one out in the
+/// order they were read, then print a summary of how it went.
+#[throws(io::Error)]
+fn solve_batch<const BW: usize, const BH: usize, const N: usize>(
+    puzzles: &[Board<BW, BH, N>],
+    solver: &dyn Solver<BW, BH, N>,
+    options: &SolveOptions,
+    jobs: usize,
+    out: &mut dyn Write,
+) {
+    let next = AtomicUsize::new(0);
+    let mut results = vec![None; puzzles.len()];
+    let (sender, receiver) = mpsc::channel();
+    let started = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let sender = sender.clone();
+            let next = &next;
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let puzzle = match puzzles.get(i) {
+                    Some(puzzle) => puzzle,
+                    None => break,
+                };
+                let report = solver.solve_with(puzzle.clone(), options);
+                let outcome = match report.solution {
+                    _ if report.stopped.is_some() => Outcome::GaveUp,
+                    None => Outcome::Unsolvable,
+                    Some(_) if puzzle.count_solutions(2) > 1 => Outcome::Multiple,
+                    Some(_) => Outcome::Solved,
+                };
+                let line = report.solution.as_ref().unwrap_or(puzzle).to_line();
+                sender
+                    .send((i, line, outcome, report.elapsed))
+                    .expect("the receiver outlives the solver threads");
+            });
+        }
+        drop(sender);
+        for (i, line, outcome, elapsed) in receiver {
+            results[i] = Some((line, outcome, elapsed));
+        }
+    });
+    let wall = started.elapsed();
+
+    let results = results
+        .into_iter()
+        .map(|result| result.expect("every puzzle gets solved"))
+        .collect::<Vec<_>>();
+    for (line, outcome, _) in results.iter() {
+        writeln!(out, "{} {}", line, outcome.name())?;
+    }
+    out.flush()?;
+
+    let mut times = results.iter().map(|r| r.2).collect::<Vec<_>>();
+    times.sort();
+    let percentile = |p: usize| match times.len() {
+        0 => Duration::default(),
+        n => times[(n - 1) * p / 100],
+    };
+    let count = |outcome| results.iter().filter(|r| r.1 == outcome).count();
+
+    // Keep the summary off stdout so the solutions can be piped on.
+    eprintln!();
+    eprintln!("Puzzles: {}", results.len());
+    eprintln!("Solved: {}", count(Outcome::Solved));
+    eprintln!("Multiple solutions: {}", count(Outcome::Multiple));
+    eprintln!("Unsolvable: {}", count(Outcome::Unsolvable));
+    eprintln!("Gave up: {}", count(Outcome::GaveUp));
+    eprintln!(
+        "Time: p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+        percentile(50),
+        percentile(90),
+        percentile(99),
+        percentile(100)
+    );
+    eprintln!("Elapsed: {:?} on {} threads", wall, jobs);
+    eprintln!();
+}
+
 /// Solve a given sudoku puzzle
 #[derive(Clap)]
 struct Solve {
     #[clap(short = "f", long = "file")]
     path: Option<String>,
-    #[clap(short = "s", long = "strategy", possible_values = &Strategy::variants(), default_value = "backtracking", case_insensitive = true)]
-    strategy: Strategy,
+    /// Solve every puzzle in this file, one per line, and summarise the results
+    #[clap(long = "batch", conflicts_with = "path")]
+    batch: Option<String>,
+    /// Write batch solutions to this file, one per line
+    #[clap(short = "o", long = "output", requires = "batch")]
+    output: Option<String>,
+    /// How many threads to solve a batch on, defaulting to one per CPU
+    #[clap(short = "j", long = "jobs", requires = "batch")]
+    jobs: Option<usize>,
+    /// The name of the solver to use, e.g. propagation or backtracking
+    #[clap(short = "s", long = "strategy", default_value = "propagation")]
+    strategy: String,
+    /// Give up after visiting this many nodes of the search
+    #[clap(long = "node-limit")]
+    node_limit: Option<u64>,
+    /// Give up after this many seconds
+    #[clap(long = "time-limit")]
+    time_limit: Option<f64>,
+    /// Show how much work the solver did
+    #[clap(long = "stats")]
+    stats: bool,
+    /// The number of values, and spaces along each side: 4, 6, 8, 9, 16 or 25
+    #[clap(long = "size", default_value = "9", conflicts_with = "multi")]
+    size: usize,
+    #[clap(flatten)]
+    variant: Variant,
+    /// Read the file as overlapping 9x9 grids, such as a Samurai: a line naming the top left
+    /// space of each grid, like `grids: r1c1 r7c7`, then a picture with blanks where there are no
+    /// spaces
+    #[clap(long = "multi", conflicts_with = "batch")]
+    multi: bool,
 }
 
 /// Explore sudoku puzzles
@@ -49,44 +309,602 @@ struct Solve {
 struct Explore {
     #[clap(short = "f", long = "file")]
     path: Option<String>,
+    /// Also search for the fewest givens that let the puzzle be solved by technique alone, which
+    /// can take a very long time on a puzzle that's far from it
+    #[clap(long = "backdoor")]
+    backdoor: bool,
+    /// The techniques the backdoor should open the puzzle up to, defaulting to
+    /// naked and hidden singles
+    #[clap(long = "techniques", use_delimiter = true, requires = "backdoor")]
+    techniques: Vec<Technique>,
+    /// The number of values, and spaces along each side: 4, 6, 8, 9, 16 or 25
+    #[clap(long = "size", default_value = "9")]
+    size: usize,
+    #[clap(flatten)]
+    variant: Variant,
+}
+
+/// Add the fewest givens needed to make a puzzle's solution unique
+#[derive(Clap)]
+struct Repair {
+    #[clap(short = "f", long = "file")]
+    path: Option<String>,
+    /// The number of values, and spaces along each side: 4, 6, 8, 9, 16 or 25
+    #[clap(long = "size", default_value = "9")]
+    size: usize,
+    #[clap(flatten)]
+    variant: Variant,
+}
+
+arg_enum! {
+    #[derive(Debug)]
+    enum Format {
+        Line,
+        Dimacs
+    }
+}
+
+/// Export a sudoku puzzle to another format
+#[derive(Clap)]
+struct Export {
+    #[clap(short = "f", long = "file")]
+    path: Option<String>,
+    #[clap(long = "format", possible_values = &Format::variants(), default_value = "line", case_insensitive = true)]
+    format: Format,
+    /// The number of values, and spaces along each side: 4, 6, 8, 9, 16 or 25
+    #[clap(long = "size", default_value = "9")]
+    size: usize,
+    #[clap(flatten)]
+    variant: Variant,
+}
+
+/// Import the model a SAT solver found for an exported puzzle
+#[derive(Clap)]
+struct Import {
+    #[clap(short = "f", long = "file")]
+    path: String,
+    /// The puzzle that was exported, to keep its regions and rules
+    #[clap(short = "p", long = "puzzle")]
+    puzzle: Option<String>,
+    /// The number of values, and spaces along each side: 4, 6, 8, 9, 16 or 25
+    #[clap(long = "size", default_value = "9")]
+    size: usize,
+    #[clap(flatten)]
+    variant: Variant,
+}
+
+arg_enum! {
+    #[derive(Debug)]
+    enum Difficulty {
+        Easy,
+        Medium,
+        Hard,
+        Expert
+    }
+}
+
+impl Difficulty {
+    fn grades(&self) -> RangeInclusive<Technique> {
+        match self {
+            Self::Easy => Technique::NakedSingle..=Technique::HiddenSingle,
+            Self::Medium => Technique::LockedCandidates..=Technique::HiddenPair,
+            Self::Hard => Technique::NakedTriple..=Technique::HiddenQuad,
+            Self::Expert => Technique::XWing..=Technique::Swordfish,
+        }
+    }
+}
+
+/// Show the puzzle of the day, which is the same for everyone
+#[derive(Clap)]
+struct Daily {
+    /// The day to show the puzzle for, as YYYY-MM-DD, defaulting to today (UTC)
+    #[clap(short = "d", long = "date")]
+    date: Option<String>,
+    #[clap(long = "difficulty", possible_values = &Difficulty::variants(), default_value = "medium", case_insensitive = true)]
+    difficulty: Difficulty,
+    /// The number of values, and spaces along each side: 4, 6, 8, 9, 16 or 25
+    #[clap(long = "size", default_value = "9")]
+    size: usize,
+}
+
+fn is_leap_year(year: u32) -> bool {
+    match (year % 4, year % 100, year % 400) {
+        (_, _, 0) => true,
+        (_, 0, _) => false,
+        (0, _, _) => true,
+        _ => false,
+    }
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
 }
 
 #[throws(Box<dyn std::error::Error>)]
-fn main() {
+fn parse_date(date: &str) -> (u32, u32, u32) {
+    let parts = date.split('-').collect::<Vec<&str>>();
+    if parts.len() != 3 {
+        throw!(format!("invalid date {:?}, expected YYYY-MM-DD", date));
+    }
+    let (year, month, day) = (parts[0].parse()?, parts[1].parse()?, parts[2].parse()?);
+    if (1..=12).contains(&month).not() || (1..=days_in_month(year, month)).contains(&day).not() {
+        throw!(format!("invalid date {:?}, expected YYYY-MM-DD", date));
+    }
+
+    (year, month, day)
+}
+
+/// Today's date in UTC.
+fn today() -> (u32, u32, u32) {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before 1970")
+        .as_secs();
+
+    date_from_days(secs / 86_400)
+}
+
+/// The date `days` days after the Unix epoch, using Howard Hinnant's
+/// `civil_from_days` algorithm.
+fn date_from_days(days: u64) -> (u32, u32, u32) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year as u32, month as u32, day as u32)
+}
+
+/// Run a function generic over the grid size with the grid type that has
+/// `$size` values, for the sizes the command line supports.
+macro_rules! with_size {
+    ($size:expr, $run:ident($($arg:expr),*)) => {
+        match $size {
+            4 => $run::<2, 2, 4>($($arg),*)?,
+            6 => $run::<3, 2, 6>($($arg),*)?,
+            8 => $run::<4, 2, 8>($($arg),*)?,
+            9 => $run::<3, 3, 9>($($arg),*)?,
+            16 => $run::<4, 4, 16>($($arg),*)?,
+            25 => $run::<5, 5, 25>($($arg),*)?,
+            size => throw!(format!("unsupported grid size {}, expected 4, 6, 8, 9, 16 or 25", size)),
+        }
+    };
+}
+
+/// Rules to add to every grid, on top of any it's written with
+#[derive(Clap)]
+struct Variant {
+    /// Make the two main diagonals hold every value too, for Sudoku-X
+    #[clap(long = "diagonal")]
+    diagonal: bool,
+    /// Forbid the same value a knight's move apart
+    #[clap(long = "anti-knight")]
+    anti_knight: bool,
+    /// Forbid the same value a king's move apart
+    #[clap(long = "anti-king")]
+    anti_king: bool,
+}
+
+/// Apply the variant rules chosen on the command line to `grid`.
+fn with_variant<const BW: usize, const BH: usize, const N: usize>(
+    grid: Board<BW, BH, N>,
+    variant: &Variant,
+) -> Board<BW, BH, N> {
+    let grid = if variant.diagonal {
+        grid.with_diagonals()
+    } else {
+        grid
+    };
+    let grid = if variant.anti_knight {
+        grid.with_anti_knight()
+    } else {
+        grid
+    };
+    if variant.anti_king {
+        grid.with_anti_king()
+    } else {
+        grid
+    }
+}
+
+/// Read a grid from `path`, or an empty grid if there's no path.
+#[throws(io::Error)]
+fn read_grid(path: &Option<String>) -> String {
+    match path {
+        Some(path) => fs::read_to_string(path)?,
+        None => String::new(),
+    }
+}
+
+#[throws(Box<dyn std::error::Error>)]
+fn generate<const BW: usize, const BH: usize, const N: usize>(c: Generate) {
     let mut stdout = stdout();
+    // The default clue range is for 9x9 grids, so scale it to the others.
+    let min_clues = Board::<BW, BH, N>::MIN_CLUES;
+    let scale = |clues: usize| (clues * N * N / 81).max(min_clues);
+    let clues = match c.clues {
+        Some(clues) => clues..=clues,
+        None => {
+            let min = c.min_clues.unwrap_or_else(|| scale(22));
+            let max = c.max_clues.unwrap_or_else(|| scale(30));
+            min..=max
+        }
+    };
+    if *clues.start() < min_clues || clues.is_empty() || *clues.end() > N * N {
+        throw!(format!(
+            "clues must be a range within {}..={}",
+            min_clues,
+            N * N
+        ));
+    }
+    let target = match c.requires {
+        _ if c.killer => Target::Killer,
+        _ if c.greater_than => Target::GreaterThan,
+        Some(technique) => Target::Technique(technique),
+        None => Target::Clues(clues),
+    };
+    let template = with_variant(Board::<BW, BH, N>::empty(), &c.variant);
+
+    if c.count > 1 || c.output.is_some() {
+        let jobs = match c.jobs {
+            Some(jobs) => jobs.max(1),
+            None => thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+        let mut out: Box<dyn Write> = match c.output {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(io::stdout()),
+        };
+        write_batch(target, template, c.count, jobs, c.rate, &mut out)?;
+        return;
+    }
+
+    let generated = target.generate(&template, &mut rand::thread_rng())?;
+    for s in generated.puzzle.to_styled() {
+        queue!(stdout, PrintStyledContent(s))?;
+    }
+    stdout.flush()?;
+    println!();
+    if c.killer {
+        println!("Cages: {}", generated.puzzle.cage_count());
+    } else {
+        println!("Clues: {}", generated.puzzle.clues());
+    }
+    if let Some(technique) = c.requires {
+        println!("Requires: {}", technique);
+    }
+    println!("Attempts: {}", generated.attempts);
+    println!();
+}
+
+#[throws(Box<dyn std::error::Error>)]
+fn solve<const BW: usize, const BH: usize, const N: usize>(c: Solve, contents: &str) {
+    let mut stdout = stdout();
+    let solvers = Solvers::<BW, BH, N>::default();
+    let solver = match solvers.get(&c.strategy) {
+        Some(solver) => solver,
+        None => throw!(format!(
+            "unknown strategy {:?}, expected one of: {}",
+            c.strategy,
+            solvers.names().join(", ")
+        )),
+    };
+    // Negative, NaN and overlong limits would panic once made a duration.
+    let time_limit = match c.time_limit {
+        Some(secs) => match Duration::try_from_secs_f64(secs) {
+            Ok(limit) => Some(limit),
+            Err(_) => throw!(format!("invalid time limit {}, expected seconds", secs)),
+        },
+        None => None,
+    };
+    let options = SolveOptions {
+        node_limit: c.node_limit,
+        time_limit,
+        ..SolveOptions::default()
+    };
+
+    if c.batch.is_some() {
+        let puzzles = Board::<BW, BH, N>::all_from_str(contents)?
+            .into_iter()
+            .map(|puzzle| with_variant(puzzle, &c.variant))
+            .collect::<Vec<_>>();
+        let jobs = match c.jobs {
+            Some(jobs) => jobs.max(1),
+            None => thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+        let mut out: Box<dyn Write> = match c.output {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(io::stdout()),
+        };
+        solve_batch(&puzzles, solver, &options, jobs, &mut out)?;
+        return;
+    }
+
+    let grid = with_variant(contents.parse::<Board<BW, BH, N>>()?, &c.variant);
+    let report = solver.solve_with(grid.clone(), &options);
+    if let Some(solution) = &report.solution {
+        for s in solution.to_styled() {
+            queue!(stdout, PrintStyledContent(s))?;
+        }
+        stdout.flush()?;
+    }
+    if c.stats {
+        println!();
+        println!("Nodes: {}", report.nodes);
+        println!("Backtracks: {}", report.backtracks);
+        println!("Guesses: {}", report.guesses);
+        println!("Elapsed: {:?}", report.elapsed);
+        println!();
+    }
+    if let Some(stop) = report.stopped {
+        throw!(format!("gave up: {}", stop));
+    }
+    if report.solution.is_none() {
+        let core = grid.unsat_core().unwrap_or_default();
+        let highlights = core
+            .iter()
+            .map(|&position| (position, Color::DarkRed))
+            .collect();
+        for s in grid.to_styled_with(&highlights) {
+            queue!(stdout, PrintStyledContent(s))?;
+        }
+        stdout.flush()?;
+        println!();
+        if core.is_empty() {
+            println!("The rules contradict each other, whatever the givens");
+        } else {
+            println!("These givens contradict each other:");
+            for (x, y) in core {
+                println!("  row {}, column {}", y + 1, x + 1);
+            }
+        }
+        println!();
+        throw!("unsolvable");
+    }
+}
+
+/// Solve a puzzle of overlapping grids all at once.
+#[throws(Box<dyn std::error::Error>)]
+fn solve_multi(contents: &str) {
+    let mut stdout = stdout();
+    let puzzle = contents.parse::<MultiGrid>()?;
+    let solution = match puzzle.solve() {
+        Some(solution) => solution,
+        None => throw!("unsolvable"),
+    };
+    for s in solution.to_styled() {
+        queue!(stdout, PrintStyledContent(s))?;
+    }
+    stdout.flush()?;
+    println!();
+    println!("Grids: {}", solution.corners().len());
+    println!();
+}
+
+#[throws(Box<dyn std::error::Error>)]
+fn explore<const BW: usize, const BH: usize, const N: usize>(c: Explore, contents: &str) {
+    let mut stdout = stdout();
+    let grid = with_variant(contents.parse::<Board<BW, BH, N>>()?, &c.variant);
+    // Fill in the spaces every solution agrees on, and mark the ones
+    // that are still open to a choice.
+    let mut overlay = grid.clone();
+    let mut highlights = HashMap::new();
+    let backbone = if grid.is_complete() {
+        None
+    } else {
+        grid.backbone()
+    };
+    if let Some(backbone) = &backbone {
+        for &((x, y), value) in backbone.forced.iter() {
+            overlay.set(x, y, value);
+            highlights.insert((x, y), Color::DarkGreen);
+        }
+        for ((x, y), _) in backbone.choices.iter() {
+            highlights.insert((*x, *y), Color::DarkYellow);
+        }
+    }
+    for s in overlay.to_styled_with(&highlights) {
+        queue!(stdout, PrintStyledContent(s))?;
+    }
+    stdout.flush()?;
+    println!();
+    println!("Complete: {}", grid.is_complete());
+    println!("Solved: {}", grid.is_solved());
+    if let Some(backbone) = backbone {
+        println!("Forced: {}", backbone.forced.len());
+        println!("Choices: {}", backbone.choices.len());
+    }
+    let backdoor = if c.backdoor.not() {
+        None
+    } else if c.techniques.is_empty() {
+        grid.backdoor()
+    } else {
+        grid.backdoor_with(&c.techniques)
+    };
+    if let Some(backdoor) = backdoor {
+        println!("Backdoor: {}", backdoor.len());
+        for (x, y) in backdoor {
+            println!("  row {}, column {}", y + 1, x + 1);
+        }
+    }
+    println!();
+}
+
+/// The puzzle for a day. The seed only depends on the date, and ChaCha's
+/// output is fixed for a given seed, unlike `StdRng`'s, so everyone
+/// generating the puzzle for a day gets the same one.
+fn daily_puzzle<const BW: usize, const BH: usize, const N: usize>(
+    (year, month, day): (u32, u32, u32),
+    difficulty: &Difficulty,
+) -> Result<Generated<BW, BH, N>, GaveUp> {
+    let seed = u64::from(year) * 10_000 + u64::from(month) * 100 + u64::from(day);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    Board::generate_graded(difficulty.grades(), &mut rng)
+}
+
+#[throws(Box<dyn std::error::Error>)]
+fn daily<const BW: usize, const BH: usize, const N: usize>(c: Daily) {
+    let mut stdout = stdout();
+    let (year, month, day) = match c.date {
+        Some(date) => parse_date(&date)?,
+        None => today(),
+    };
+    let generated = daily_puzzle::<BW, BH, N>((year, month, day), &c.difficulty)?;
+    println!(
+        "Daily puzzle for {:04}-{:02}-{:02} ({:?})",
+        year, month, day, c.difficulty
+    );
+    for s in generated.puzzle.to_styled() {
+        queue!(stdout, PrintStyledContent(s))?;
+    }
+    stdout.flush()?;
+    println!();
+    println!("Clues: {}", generated.puzzle.clues());
+    println!();
+}
+
+#[throws(Box<dyn std::error::Error>)]
+fn export<const BW: usize, const BH: usize, const N: usize>(c: Export) {
+    let grid = match c.path {
+        Some(path) => Board::<BW, BH, N>::from_file(&path)?,
+        None => Board::empty(),
+    };
+    let grid = with_variant(grid, &c.variant);
+    match c.format {
+        Format::Line => println!("{}", grid.to_line()),
+        Format::Dimacs if grid.has_constraints() => {
+            throw!("only the units of a grid can be exported to DIMACS")
+        }
+        Format::Dimacs => print!("{}", grid.to_dimacs()),
+    }
+}
+
+#[throws(Box<dyn std::error::Error>)]
+fn import<const BW: usize, const BH: usize, const N: usize>(c: Import) {
+    let mut stdout = stdout();
+    let template = match c.puzzle {
+        Some(path) => Board::<BW, BH, N>::from_file(&path)?,
+        None => Board::empty(),
+    };
+    let template = with_variant(template, &c.variant);
+    let grid = template.with_sat_model(&fs::read_to_string(&c.path)?)?;
+    for s in grid.to_styled() {
+        queue!(stdout, PrintStyledContent(s))?;
+    }
+    stdout.flush()?;
+    println!();
+    println!("Solved: {}", grid.is_solved());
+    println!();
+}
+
+#[throws(Box<dyn std::error::Error>)]
+fn repair<const BW: usize, const BH: usize, const N: usize>(c: Repair) {
+    let mut stdout = stdout();
+    let grid = match c.path {
+        Some(path) => Board::<BW, BH, N>::from_file(&path)?,
+        None => Board::empty(),
+    };
+    let mut grid = with_variant(grid, &c.variant);
+    let givens = match grid.disambiguate() {
+        Some(givens) => givens,
+        None => throw!("unsolvable"),
+    };
+    if givens.is_empty() {
+        println!("The puzzle already has a unique solution");
+        return;
+    }
+
+    let mut highlights = HashMap::new();
+    for &((x, y), value) in givens.iter() {
+        grid.set(x, y, value);
+        highlights.insert((x, y), Color::DarkGreen);
+    }
+    for s in grid.to_styled_with(&highlights) {
+        queue!(stdout, PrintStyledContent(s))?;
+    }
+    stdout.flush()?;
+    println!();
+    println!("Add these givens to make the solution unique:");
+    for ((x, y), value) in givens {
+        println!("  row {}, column {}: {}", y + 1, x + 1, value);
+    }
+    println!();
+}
+
+#[throws(Box<dyn std::error::Error>)]
+fn main() {
     let opts = Opts::parse();
 
     match opts.subcmd {
-        Subcommand::Generate(_) => {
-            todo!();
-        }
+        Subcommand::Generate(c) => with_size!(c.size, generate(c)),
+        Subcommand::Solve(c) if c.multi => solve_multi(&read_grid(&c.path)?)?,
         Subcommand::Solve(c) => {
-            let grid = match c.path {
-                Some(path) => Grid::from_file(&path)?,
-                None => Grid::empty(),
+            let contents = match &c.batch {
+                Some(batch) => fs::read_to_string(batch)?,
+                None => read_grid(&c.path)?,
             };
-            let grid = match c.strategy {
-                Strategy::Backtracking => grid.backtracking_solve().expect("Unsolvable"),
-                Strategy::Stochastic => grid.stochastic_solve().expect("Unsolvable"),
-            };
-            for s in grid.to_styled() {
-                queue!(stdout, PrintStyledContent(s))?;
-            }
-            stdout.flush()?;
+            with_size!(c.size, solve(c, &contents))
         }
+        Subcommand::Daily(c) => with_size!(c.size, daily(c)),
+        Subcommand::Export(c) => with_size!(c.size, export(c)),
+        Subcommand::Import(c) => with_size!(c.size, import(c)),
         Subcommand::Explore(c) => {
-            let grid = match c.path {
-                Some(path) => Grid::from_file(&path)?,
-                None => Grid::empty(),
-            };
-            for s in grid.to_styled() {
-                queue!(stdout, PrintStyledContent(s))?;
-            }
-            stdout.flush()?;
-            println!();
-            println!("Complete: {}", grid.is_complete());
-            println!("Solved: {}", grid.is_solved());
-            println!();
+            let contents = read_grid(&c.path)?;
+            with_size!(c.size, explore(c, &contents))
         }
+        Subcommand::Repair(c) => with_size!(c.size, repair(c)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("2024-01-02", (2024, 1, 2))]
+    #[test_case("2000-02-29", (2000, 2, 29))]
+    #[test_case("1999-12-31", (1999, 12, 31))]
+    fn dates_parse(date: &str, expected: (u32, u32, u32)) {
+        assert_eq!(parse_date(date).ok(), Some(expected));
+    }
+
+    #[test_case("2024-01" ; "missing day")]
+    #[test_case("2024-13-01" ; "month")]
+    #[test_case("2023-02-29" ; "not a leap year")]
+    #[test_case("1900-02-29" ; "century")]
+    #[test_case("2024-01-x" ; "not a number")]
+    fn dates_reject(date: &str) {
+        assert!(parse_date(date).is_err());
+    }
+
+    #[test_case(0, (1970, 1, 1))]
+    #[test_case(11_016, (2000, 2, 29))]
+    #[test_case(19_723, (2024, 1, 1))]
+    #[test_case(47_541, (2100, 3, 1))]
+    fn days_since_the_epoch(days: u64, expected: (u32, u32, u32)) {
+        assert_eq!(date_from_days(days), expected);
+    }
+
+    #[test]
+    fn daily_puzzle_is_fixed_by_its_date() {
+        let puzzle = daily_puzzle::<3, 3, 9>((2024, 1, 2), &Difficulty::Easy)
+            .unwrap()
+            .puzzle;
+
+        assert_eq!(
+            puzzle.to_line(),
+            "74....6.....9....53......4......75.2...3.4....3..12...8..........9....6...2..5.17"
+        );
     }
 }
@@ -0,0 +1,97 @@
+use std::{fmt, iter, iter::FromIterator};
+
+use crate::value::Value;
+
+/// A set of values, stored as a bitmask so that it is cheap to copy.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Candidates(u32);
+
+fn bit(value: Value) -> u32 {
+    1 << (u8::from(value) - 1)
+}
+
+impl Candidates {
+    /// Every value in a grid with `size` of them.
+    pub fn all(size: usize) -> Self {
+        Value::all(size).collect()
+    }
+
+    pub fn none() -> Self {
+        Self(0)
+    }
+
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, value: Value) -> bool {
+        self.0 & bit(value) != 0
+    }
+
+    pub fn insert(&mut self, value: Value) {
+        self.0 |= bit(value);
+    }
+
+    /// Remove `value`, returning whether it was present.
+    pub fn remove(&mut self, value: Value) -> bool {
+        let present = self.contains(value);
+        self.0 &= !bit(value);
+
+        present
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    pub fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// The only value in the set, if it has exactly one.
+    pub fn single(self) -> Option<Value> {
+        if self.len() == 1 {
+            self.iter().next()
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Value> {
+        let mut bits = self.0;
+        iter::from_fn(move || {
+            if bits == 0 {
+                return None;
+            }
+            let lowest = bits.trailing_zeros() as u8;
+            bits &= bits - 1;
+
+            Some(Value::from(lowest + 1))
+        })
+    }
+}
+
+impl FromIterator<Value> for Candidates {
+    fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Self {
+        let mut candidates = Self::none();
+        for value in iter {
+            candidates.insert(value);
+        }
+
+        candidates
+    }
+}
+
+impl fmt::Debug for Candidates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
@@ -1,121 +1,473 @@
 use std::{
+    cell::RefCell,
     collections::{HashMap, HashSet},
     convert::TryInto,
+    error::Error,
     fmt,
     fs::File,
     io::{prelude::*, BufReader},
-    num::ParseIntError,
-    ops::Not,
+    ops::{Index, IndexMut, Not},
     str::FromStr,
+    sync::{Arc, Mutex, Weak},
 };
 
-use fehler::throws;
+use fehler::{throw, throws};
+use lazy_static::lazy_static;
 use rand::prelude::*;
 
 use crate::{
+    solver::{Search, SolveOptions, SolveReport},
     space::Space,
-    value::{Value, ALL_VALUES},
+    value::Value,
 };
 
+mod backbone;
+mod backdoor;
+mod chess;
+mod compare;
+mod constraint;
+mod dimacs;
+mod dots;
+mod generate;
+mod killer;
+mod lines;
+mod logic;
+mod multi;
+mod propagate;
+mod repair;
+mod sandwich;
 mod styled;
+mod unsat;
 
-#[derive(Clone, Copy)]
-pub struct Grid {
-    spaces: [Space; 81],
+pub use backbone::Backbone;
+pub use constraint::{Constraint, Hint, Propagate};
+pub use generate::{GaveUp, Generated};
+pub use logic::LogicalSolve;
+pub use multi::MultiGrid;
+
+use constraint::{Rules, Unique};
+use propagate::Propagator;
+
+/// How a grid is divided up: into rows and columns, and into regions that
+/// must each hold every value once, which are usually the boxes. Sudoku-X
+/// grids add the two main diagonals.
+struct Layout {
+    /// The region each space is in.
+    region_of: Vec<usize>,
+    /// Whether the two main diagonals must hold every value too.
+    diagonal: bool,
+    /// The width and height of each shape of box whose boxes are the
+    /// regions, none for a jigsaw.
+    shapes: Vec<(usize, usize)>,
+    /// The spaces in each row, then each column, then each region, then each
+    /// diagonal if there are any, which must all hold different values.
+    units: Vec<Unique>,
+    /// For each space, the indices of every other space sharing a unit with
+    /// it.
+    peers: Vec<Vec<usize>>,
+    /// For each space, the indices of the units it's in.
+    units_of: Vec<Vec<usize>>,
+}
+
+/// A layout's regions, by the region of each space, and whether it has
+/// diagonals.
+type LayoutKey = (Vec<usize>, bool);
+
+lazy_static! {
+    /// Every layout in use, so that grids with the same one share it. A
+    /// layout is dropped once no grid uses it.
+    static ref LAYOUTS: Mutex<HashMap<LayoutKey, Weak<Layout>>> = Mutex::new(HashMap::new());
+}
+
+thread_local! {
+    /// The layout of each shape of box this thread has used, so that empty
+    /// grids can be made without waiting on `LAYOUTS`.
+    static BOXES: RefCell<HashMap<(usize, usize), Arc<Layout>>> = RefCell::default();
+}
+
+/// The region of each space of a grid with boxes `box_width` wide and
+/// `box_height` tall.
+fn box_regions(box_width: usize, box_height: usize) -> Vec<usize> {
+    let size = box_width * box_height;
+
+    (0..size * size)
+        .map(|i| {
+            let (x, y) = (i % size, i / size);
+            (y / box_height) * box_height + x / box_width
+        })
+        .collect()
 }
 
-fn is_solved(spaces: Vec<Space>) -> bool {
-    let mut values = vec![];
-    for space in spaces {
-        match space {
-            Space::Empty => return false,
-            Space::Occupied(value) => values.push(value),
+impl Layout {
+    /// The layout of a grid with boxes `box_width` wide and `box_height`
+    /// tall.
+    fn boxes(box_width: usize, box_height: usize) -> Arc<Self> {
+        BOXES.with(|boxes| {
+            let mut boxes = boxes.borrow_mut();
+            let layout = boxes
+                .entry((box_width, box_height))
+                .or_insert_with(|| Self::interned(box_regions(box_width, box_height), false));
+
+            Arc::clone(layout)
+        })
+    }
+
+    /// The one layout with these regions, where the regions are numbered in
+    /// the order they're first met.
+    fn interned(region_of: Vec<usize>, diagonal: bool) -> Arc<Self> {
+        let mut layouts = LAYOUTS.lock().unwrap();
+        let key = (region_of, diagonal);
+        if let Some(layout) = layouts.get(&key).and_then(Weak::upgrade) {
+            return layout;
         }
+
+        // Forget the layouts no grid uses any more before adding this one,
+        // so reading many jigsaw puzzles doesn't keep every layout around.
+        layouts.retain(|_, layout| layout.strong_count() > 0);
+        let layout = Arc::new(Self::new(key.0.clone(), diagonal));
+        layouts.insert(key, Arc::downgrade(&layout));
+
+        layout
     }
-    values.sort();
 
-    let mut all_values = ALL_VALUES.iter().cloned().collect::<Vec<Value>>();
-    all_values.sort();
+    /// Lay out a square grid given the region of each space, where there are
+    /// as many regions as values, and as many spaces in each.
+    fn new(region_of: Vec<usize>, diagonal: bool) -> Self {
+        let spaces = region_of.len();
+        let size = (1..=spaces).find(|size| size * size >= spaces).unwrap();
 
-    values == all_values
-}
+        let mut units: Vec<Vec<usize>> = vec![];
+        for y in 0..size {
+            units.push((0..size).map(|x| x + y * size).collect());
+        }
+        for x in 0..size {
+            units.push((0..size).map(|y| x + y * size).collect());
+        }
+        for region in 0..size {
+            units.push((0..spaces).filter(|&i| region_of[i] == region).collect());
+        }
+        if diagonal {
+            units.push((0..size).map(|n| n + n * size).collect());
+            units.push((0..size).map(|n| size - 1 - n + n * size).collect());
+        }
+
+        let peers = (0..spaces)
+            .map(|i| {
+                let mut peers = units
+                    .iter()
+                    .filter(|unit| unit.contains(&i))
+                    .flatten()
+                    .cloned()
+                    .filter(|&p| p != i)
+                    .collect::<Vec<usize>>();
+                peers.sort();
+                peers.dedup();
+
+                peers
+            })
+            .collect();
+
+        let units_of = (0..spaces)
+            .map(|i| {
+                (0..units.len())
+                    .filter(|&u| units[u].contains(&i))
+                    .collect()
+            })
+            .collect();
+
+        let shapes = (1..=size)
+            .filter(|width| size % width == 0)
+            .map(|width| (width, size / width))
+            .filter(|&(width, height)| box_regions(width, height) == region_of)
+            .collect();
+
+        // Only the diagonals need picking out, as the rest are drawn as
+        // borders.
+        let units = units
+            .into_iter()
+            .enumerate()
+            .map(|(u, unit)| Unique::new(unit, u >= size * 3))
+            .collect();
 
-impl Grid {
-    pub fn empty() -> Self {
         Self {
-            spaces: [Space::Empty; 81],
+            region_of,
+            diagonal,
+            shapes,
+            units,
+            peers,
+            units_of,
         }
     }
+}
 
-    pub fn new_solved() -> Self {
-        let grid = Self {
-            spaces: [Space::Empty; 81],
-        };
-        grid.solve().unwrap()
+/// One of something for every space of an `N` by `N` grid, indexed in
+/// row-major order.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Cells<T, const N: usize>([[T; N]; N]);
+
+impl<T: Copy, const N: usize> Cells<T, N> {
+    fn filled(value: T) -> Self {
+        Self([[value; N]; N])
     }
 
-    fn get(&self, x: u8, y: u8) -> Space {
-        let i: usize = (x + (y * 9)).try_into().unwrap();
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        self.0.iter().flatten()
+    }
 
-        self.spaces[i]
+    fn as_slice(&self) -> &[T] {
+        self.0.as_flattened()
     }
+}
 
-    fn rows(&self) -> Vec<Vec<Space>> {
-        let mut rows = vec![];
-        for y in 0..=8 {
-            let mut row = vec![];
-            for x in 0..=8 {
-                row.push(self.get(x, y));
-            }
-            rows.push(row);
-        }
+impl<T, const N: usize> Index<usize> for Cells<T, N> {
+    type Output = T;
 
-        rows
+    fn index(&self, i: usize) -> &T {
+        &self.0[i / N][i % N]
     }
+}
+
+impl<T, const N: usize> IndexMut<usize> for Cells<T, N> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        &mut self.0[i / N][i % N]
+    }
+}
+
+/// A sudoku grid of `N` by `N` spaces, divided into boxes `BW` spaces wide
+/// and `BH` tall. `N` must be `BW * BH`, which is checked when the grid is
+/// built. A jigsaw grid swaps the boxes for irregular regions of `N` spaces,
+/// and a killer grid adds cages of spaces with a sum. Any other rule can be
+/// added as a `Constraint`.
+#[derive(Clone)]
+pub struct Board<const BW: usize, const BH: usize, const N: usize> {
+    spaces: Cells<Space, N>,
+    layout: Arc<Layout>,
+    rules: Arc<Rules>,
+}
+
+/// The classic 9x9 grid, with 3x3 boxes.
+pub type Grid = Board<3, 3, 9>;
+
+/// A 4x4 grid, with 2x2 boxes.
+pub type Grid4 = Board<2, 2, 4>;
+
+/// A 6x6 grid, with boxes 3 wide and 2 tall.
+pub type Grid6 = Board<3, 2, 6>;
+
+/// An 8x8 grid, with boxes 4 wide and 2 tall.
+pub type Grid8 = Board<4, 2, 8>;
+
+/// A 16x16 grid, with 4x4 boxes.
+pub type Grid16 = Board<4, 4, 16>;
 
-    fn columns(&self) -> Vec<Vec<Space>> {
-        let mut columns = vec![];
-        for x in 0..=8 {
-            let mut column = vec![];
-            for y in 0..=8 {
-                column.push(self.get(x, y));
+/// A 25x25 grid, with 5x5 boxes.
+pub type Grid25 = Board<5, 5, 25>;
+
+/// The spaces next to space `i` across or down, in a grid `size` spaces wide.
+fn neighbours(i: usize, size: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = (i % size, i / size);
+    let neighbours = vec![
+        (x > 0).then(|| i - 1),
+        (x + 1 < size).then(|| i + 1),
+        (y > 0).then(|| i - size),
+        (y + 1 < size).then(|| i + size),
+    ];
+
+    neighbours.into_iter().flatten()
+}
+
+/// The spaces reachable from `start` by steps across or down between spaces
+/// that are all `inside`, in the order they're reached.
+fn reachable(start: usize, size: usize, inside: impl Fn(usize) -> bool) -> Vec<usize> {
+    let mut reached = vec![start];
+    let mut next = 0;
+    while next < reached.len() {
+        for n in neighbours(reached[next], size) {
+            if inside(n) && reached.contains(&n).not() {
+                reached.push(n);
             }
-            columns.push(column);
         }
+        next += 1;
+    }
+
+    reached
+}
+
+/// Whether `region` has `size` spaces, each reachable from the others by
+/// steps across or down between spaces of the region.
+fn is_connected_region(region_of: &[usize], region: usize, size: usize) -> bool {
+    let spaces = (0..size * size)
+        .filter(|&i| region_of[i] == region)
+        .collect::<Vec<usize>>();
+
+    spaces.len() == size && reachable(spaces[0], size, |n| region_of[n] == region).len() == size
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    const SHAPE: () = assert!(
+        BW * BH == N && N <= Value::MAX as usize,
+        "a grid must be BW * BH spaces across, with at most 25 values",
+    );
+
+    /// The number of spaces on each side of the grid, and of values.
+    pub const SIZE: usize = N;
+
+    pub fn empty() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::SHAPE;
+
+        Self {
+            spaces: Cells::filled(Space::Empty),
+            layout: Layout::boxes(BW, BH),
+            rules: Arc::default(),
+        }
+    }
+
+    /// A solved grid, found by constraint propagation, which copes with
+    /// grids of every size.
+    pub fn new_solved() -> Self {
+        Self::empty().propagation_solve().unwrap()
+    }
 
-        columns
+    pub fn new_solved_with_rng<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::empty().randomised_solve(rng).unwrap()
     }
 
-    fn squares(&self) -> Vec<Vec<Space>> {
-        let mut squares = vec![];
-        for square_y in 0..=2 {
-            for square_x in 0..=2 {
-                let mut square = vec![];
-                for local_y in 0..=2 {
-                    let grid_y = local_y + (square_y * 3);
-                    for local_x in 0..=2 {
-                        let grid_x = local_x + (square_x * 3);
-                        let val = self.get(grid_x, grid_y);
-                        square.push(val);
-                    }
+    /// Swap the boxes for the regions in `regions`, which has a symbol for
+    /// each space in row-major order. Spaces with the same symbol are in the
+    /// same region, and each region must be `N` spaces joined up side to
+    /// side.
+    pub fn with_regions(mut self, regions: &str) -> Result<Self, ParseGridError> {
+        let symbols = regions
+            .chars()
+            .filter(|c| c.is_whitespace().not())
+            .collect::<Vec<char>>();
+        if symbols.len() != N * N {
+            return Err(ParseGridError::RegionSpaces(symbols.len()));
+        }
+
+        let mut seen = vec![];
+        let mut region_of = vec![];
+        for &symbol in symbols.iter() {
+            if symbol != '.' && symbol.is_ascii_alphanumeric().not() {
+                return Err(ParseGridError::InvalidRegion(symbol));
+            }
+            let region = match seen.iter().position(|&s| s == symbol) {
+                Some(region) => region,
+                None => {
+                    seen.push(symbol);
+                    seen.len() - 1
                 }
-                squares.push(square)
+            };
+            region_of.push(region);
+        }
+        for (region, &symbol) in seen.iter().enumerate() {
+            if is_connected_region(&region_of, region, N).not() {
+                return Err(ParseGridError::InvalidRegion(symbol));
             }
         }
 
-        squares
+        self.layout = Layout::interned(region_of, self.layout.diagonal);
+
+        Ok(self)
+    }
+
+    /// Whether the grid has irregular regions in place of its boxes.
+    pub fn is_jigsaw(&self) -> bool {
+        self.layout.shapes.contains(&(BW, BH)).not()
     }
 
-    fn set(&mut self, x: u8, y: u8, v: Value) {
-        let i: usize = (x + (y * 9)).try_into().unwrap();
+    /// Make the two main diagonals hold every value as well, for Sudoku-X.
+    pub fn with_diagonals(mut self) -> Self {
+        self.layout = Layout::interned(self.layout.region_of.clone(), true);
 
-        self.spaces[i] = Space::Occupied(v);
+        self
+    }
+
+    /// Whether the main diagonals must hold every value.
+    pub fn is_diagonal(&self) -> bool {
+        self.layout.diagonal
+    }
+
+    /// Add a rule of its own to the grid, which solving, checking and
+    /// drawing the grid all take into account.
+    pub fn with_constraint(mut self, constraint: impl Constraint + 'static) -> Self {
+        self.rules = Arc::new(self.rules.with(Arc::new(constraint), N * N));
+
+        self
+    }
+
+    /// Whether the grid has rules beyond its units, such as killer cages or
+    /// other constraints.
+    pub fn has_constraints(&self) -> bool {
+        self.rules.iter().next().is_some()
+    }
+
+    /// Every rule of the grid: its units, then its cages, then its dots, then
+    /// any other constraints.
+    fn constraints(&self) -> impl Iterator<Item = &dyn Constraint> {
+        let units = self.layout.units.iter().map(|unit| unit as &dyn Constraint);
+        units.chain(self.rules.iter())
+    }
+
+    /// The rules covering space `i`.
+    fn constraints_of(&self, i: usize) -> impl Iterator<Item = &dyn Constraint> {
+        let layout = &self.layout;
+        let units = layout.units_of[i]
+            .iter()
+            .map(move |&u| &layout.units[u] as &dyn Constraint);
+        units.chain(self.rules.of(i).iter().map(move |&r| self.rules.get(r)))
+    }
+
+    /// The same grid with every space emptied, keeping its regions,
+    /// diagonals, cages and constraints.
+    fn cleared(&self) -> Self {
+        Self {
+            spaces: Cells::filled(Space::Empty),
+            layout: Arc::clone(&self.layout),
+            rules: Arc::clone(&self.rules),
+        }
+    }
+
+    /// The (x, y) position of the space at index `i`.
+    fn position(i: usize) -> (u8, u8) {
+        let (x, y) = (i % N, i / N);
+
+        (x.try_into().unwrap(), y.try_into().unwrap())
+    }
+
+    fn index(x: u8, y: u8) -> usize {
+        usize::from(x) + usize::from(y) * N
+    }
+
+    fn get(&self, x: u8, y: u8) -> Space {
+        self.spaces[Self::index(x, y)]
+    }
+
+    pub fn set(&mut self, x: u8, y: u8, v: Value) {
+        self.spaces[Self::index(x, y)] = Space::Occupied(v);
     }
 
     fn set_empty(&mut self, x: u8, y: u8) {
-        let i: usize = (x + (y * 9)).try_into().unwrap();
+        self.spaces[Self::index(x, y)] = Space::Empty;
+    }
+
+    fn occupied_positions(&self) -> Vec<(u8, u8)> {
+        (0..N * N)
+            .filter(|&i| self.spaces[i].is_occupied())
+            .map(Self::position)
+            .collect()
+    }
 
-        self.spaces[i] = Space::Empty;
+    fn empty_positions(&self) -> Vec<(u8, u8)> {
+        (0..N * N)
+            .filter(|&i| self.spaces[i].is_occupied().not())
+            .map(Self::position)
+            .collect()
+    }
+
+    pub fn clues(&self) -> usize {
+        self.spaces.iter().filter(|s| s.is_occupied()).count()
     }
 
     pub fn is_complete(&self) -> bool {
@@ -123,76 +475,56 @@ impl Grid {
     }
 
     pub fn is_solved(&self) -> bool {
-        self.rows().iter().all(|r| is_solved(r.to_vec()))
-            && self.columns().iter().all(|c| is_solved(c.to_vec()))
-            && self.squares().iter().all(|s| is_solved(s.to_vec()))
+        let spaces = self.spaces.as_slice();
+        self.is_complete() && self.constraints().all(|c| c.holds(spaces))
     }
 
-    fn column_constraints(&self, x: u8) -> Vec<Value> {
-        let mut c = vec![];
-        for y in 0..=8 {
-            let val = match self.get(x, y) {
-                Space::Occupied(v) => v,
-                _ => continue,
-            };
-            c.push(val);
-        }
+    fn candidates(&self, x: u8, y: u8) -> HashSet<Value> {
+        let i = Self::index(x, y);
+        let spaces = self.spaces.as_slice();
+        Value::all(N)
+            .filter(|&value| self.constraints_of(i).all(|c| c.allows(spaces, i, value)))
+            .collect()
+    }
 
-        c
+    pub fn backtracking_solve(self) -> Option<Self> {
+        self.backtracking_solve_with(&SolveOptions::default())
+            .solution
     }
 
-    fn row_constraints(&self, y: u8) -> Vec<Value> {
-        let mut r = vec![];
-        for x in 0..=8 {
-            let val = match self.get(x, y) {
-                Space::Occupied(v) => v,
-                _ => continue,
-            };
-            r.push(val);
-        }
+    pub fn backtracking_solve_with(self, options: &SolveOptions) -> SolveReport<BW, BH, N> {
+        let mut search = Search::new(options);
+        let solution = self.backtrack(&mut search);
 
-        r
+        search.report(solution)
     }
 
-    fn square_constraints(&self, x: u8, y: u8) -> Vec<Value> {
-        let square_x = x / 3;
-        let square_y = y / 3;
-
-        let mut s = vec![];
-        for local_y in 0..=2 {
-            let grid_y = local_y + (square_y * 3);
-            for local_x in 0..=2 {
-                let grid_x = local_x + (square_x * 3);
-                let val = match self.get(grid_x, grid_y) {
-                    Space::Occupied(v) => v,
-                    _ => continue,
-                };
-                s.push(val);
-            }
+    fn backtrack(mut self, search: &mut Search) -> Option<Self> {
+        if search.visit().not() {
+            return None;
         }
 
-        s
-    }
-
-    pub fn backtracking_solve(mut self) -> Option<Self> {
-        for y in 0..=8 {
-            for x in 0..=8 {
+        for y in 0..N as u8 {
+            for x in 0..N as u8 {
                 let space = self.get(x, y);
                 if space.is_occupied() {
                     continue;
                 }
 
-                let mut constraints = HashSet::new();
-                constraints.extend(self.column_constraints(x));
-                constraints.extend(self.row_constraints(y));
-                constraints.extend(self.square_constraints(x, y));
-                constraints = constraints;
-
-                for value in ALL_VALUES.difference(&constraints) {
-                    self.set(x, y, *value);
-                    match self.solve() {
+                let candidates = self.candidates(x, y);
+                let guessing = candidates.len() > 1;
+                for value in candidates {
+                    if guessing {
+                        search.guesses += 1;
+                    }
+                    self.set(x, y, value);
+                    match self.clone().backtrack(search) {
                         Some(solution) => return Some(solution),
-                        None => self.set_empty(x, y),
+                        None if search.is_stopped() => return None,
+                        None => {
+                            search.backtracks += 1;
+                            self.set_empty(x, y);
+                        }
                     }
                 }
 
@@ -203,11 +535,46 @@ impl Grid {
         Some(self)
     }
 
-    pub fn stochastic_solve(mut self) -> Option<Self> {
+    /// Fill in the grid at random, guessing where propagation runs out, so
+    /// that even the largest grids fill quickly.
+    fn randomised_solve<R: Rng + ?Sized>(self, rng: &mut R) -> Option<Self> {
+        Propagator::new(&self)?.randomised_solution(rng)
+    }
+
+    /// Find the solutions of this grid, stopping once `limit` are found.
+    pub fn solutions(&self, limit: usize) -> Vec<Self> {
+        match Propagator::new(self) {
+            Some(propagator) => propagator.solutions(limit),
+            None => vec![],
+        }
+    }
+
+    /// Count the solutions of this grid, stopping once `limit` is reached.
+    pub fn count_solutions(&self, limit: usize) -> usize {
+        self.solutions(limit).len()
+    }
+
+    pub fn has_unique_solution(&self) -> bool {
+        self.count_solutions(2) == 1
+    }
+
+    pub fn stochastic_solve(self) -> Option<Self> {
+        self.stochastic_solve_with(&SolveOptions::default())
+            .solution
+    }
+
+    pub fn stochastic_solve_with(self, options: &SolveOptions) -> SolveReport<BW, BH, N> {
+        let mut search = Search::new(options);
+        let solution = self.shuffle_until_solved(&mut search);
+
+        search.report(solution)
+    }
+
+    fn shuffle_until_solved(mut self, search: &mut Search) -> Option<Self> {
         // 1) Find out if it's solvable, return early if not
         // TODO
         // 2) Determine which numbers are missing from the grid
-        let mut counts: HashMap<Value, u8> = HashMap::new();
+        let mut counts: HashMap<Value, usize> = HashMap::new();
         for space in self.spaces.iter() {
             match space {
                 Space::Empty => continue,
@@ -215,24 +582,29 @@ impl Grid {
             }
         }
         let mut values = vec![];
-        for value in ALL_VALUES.iter() {
-            let missing = match counts.get(value) {
-                Some(count) => 9 - count,
-                None => 9,
+        for value in Value::all(N) {
+            let missing = match counts.get(&value) {
+                Some(count) => N.saturating_sub(*count),
+                None => N,
             };
             for _ in 0..missing {
                 values.push(value);
             }
         }
         // 3) Shuffle the numbers and insert into the empty spaces
-        let self_clone = self;
+        let self_clone = self.clone();
         loop {
+            if search.visit().not() {
+                return None;
+            }
+            search.guesses += 1;
+
             let mut values_clone = values.clone();
             values_clone.shuffle(&mut rand::thread_rng());
 
             for (i, space) in self_clone.spaces.iter().enumerate() {
                 match space {
-                    Space::Empty => self.spaces[i] = Space::Occupied(*values_clone.pop().unwrap()),
+                    Space::Empty => self.spaces[i] = Space::Occupied(values_clone.pop().unwrap()),
                     Space::Occupied(_) => continue,
                 }
             }
@@ -240,30 +612,63 @@ impl Grid {
             if self.is_solved() {
                 return Some(self);
             }
-            println!("{}", self);
+            search.backtracks += 1;
         }
     }
 
     pub fn solve(self) -> Option<Self> {
         self.backtracking_solve()
     }
+
+    /// The grid as a single line of `N * N` characters, with `.` for empty
+    /// spaces. A jigsaw grid follows on with `N * N` more, giving each
+    /// space's region as a value symbol, and a killer grid with its cages
+    /// and their sums, as read by `with_cages`. Last comes each constraint
+    /// that can be written down, after a `;`, starting with `diagonal` for
+    /// Sudoku-X.
+    pub fn to_line(&self) -> String {
+        let mut line = self
+            .spaces
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<String>();
+        if self.is_jigsaw() {
+            for &region in self.layout.region_of.iter() {
+                line.push(Value::from(region as u8 + 1).symbol());
+            }
+        }
+        if self.is_killer() {
+            line.push_str(&self.cages_line());
+        }
+        if self.is_diagonal() {
+            line.push_str(";diagonal");
+        }
+        for rule in self.rules.iter().filter_map(|rule| rule.to_line()) {
+            line.push(';');
+            line.push_str(&rule);
+        }
+
+        line
+    }
 }
 
-impl PartialEq for Grid {
+impl<const BW: usize, const BH: usize, const N: usize> PartialEq for Board<BW, BH, N> {
     fn eq(&self, other: &Self) -> bool {
-        self.spaces.iter().eq(other.spaces.iter())
+        self.spaces == other.spaces
+            && Arc::ptr_eq(&self.layout, &other.layout)
+            && self.rules == other.rules
     }
 }
 
-impl Eq for Grid {}
+impl<const BW: usize, const BH: usize, const N: usize> Eq for Board<BW, BH, N> {}
 
-impl fmt::Debug for Grid {
+impl<const BW: usize, const BH: usize, const N: usize> fmt::Debug for Board<BW, BH, N> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.spaces.to_vec())
+        write!(f, "{:?}", self.spaces.iter().collect::<Vec<&Space>>())
     }
 }
 
-impl fmt::Display for Grid {
+impl<const BW: usize, const BH: usize, const N: usize> fmt::Display for Board<BW, BH, N> {
     #[throws(fmt::Error)]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) {
         for val in self.to_styled() {
@@ -272,35 +677,164 @@ impl fmt::Display for Grid {
     }
 }
 
-impl From<Vec<u8>> for Grid {
+impl<const BW: usize, const BH: usize, const N: usize> From<Vec<u8>> for Board<BW, BH, N> {
     fn from(numbers: Vec<u8>) -> Self {
-        let mut spaces = [Space::Empty; 81];
+        let mut grid = Self::empty();
         for (i, number) in numbers.iter().enumerate() {
-            spaces[i] = Space::from(*number);
+            grid.spaces[i] = Space::from(*number);
         }
 
-        Self { spaces }
+        grid
     }
 }
 
-impl FromStr for Grid {
-    type Err = ParseIntError;
+/// Why a grid couldn't be parsed.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ParseGridError {
+    /// A symbol that doesn't stand for any value in a grid of this size.
+    InvalidSymbol(char),
+    /// More spaces than fit in a grid of this size.
+    TooManySpaces(usize),
+    /// A region layer with the wrong number of spaces.
+    RegionSpaces(usize),
+    /// A region that isn't the right number of spaces all joined up, or a
+    /// symbol that can't name a region.
+    InvalidRegion(char),
+    /// A cage layer with the wrong number of spaces.
+    CageSpaces(usize),
+    /// A cage sum that isn't a number.
+    InvalidSum(String),
+    /// A different number of sums to cages.
+    CageSums { cages: usize, sums: usize },
+    /// A cage, counting from 1 in the order they're first met, that has more
+    /// spaces than values or a sum its spaces can't add up to.
+    InvalidCage(usize),
+    /// A constraint of a kind there's no rule for.
+    UnknownConstraint(String),
+    /// A cell that isn't written like `r1c2`, or is outside the grid.
+    InvalidCell(String),
+    /// A path that skips between spaces that don't touch, visits a space
+    /// twice, or is the wrong length for its constraint.
+    InvalidPath(String),
+    /// A clue that doesn't name a row, a column or two neighbouring spaces
+    /// inside the grid, or has a sum it can't add up to.
+    InvalidClue(String),
+    /// A picture of overlapping grids with no whole grid in it, or with
+    /// spaces outside every grid.
+    InvalidLayout,
+}
 
+impl fmt::Display for ParseGridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSymbol(symbol) => write!(f, "invalid symbol {:?}", symbol),
+            Self::TooManySpaces(size) => write!(f, "too many spaces for a {0}x{0} grid", size),
+            Self::RegionSpaces(count) => {
+                write!(f, "expected a region for every space, found {}", count)
+            }
+            Self::InvalidRegion(symbol) => write!(f, "invalid region {:?}", symbol),
+            Self::CageSpaces(count) => {
+                write!(f, "expected a cage for every space, found {}", count)
+            }
+            Self::InvalidSum(sum) => write!(f, "invalid cage sum {:?}", sum),
+            Self::CageSums { cages, sums } => write!(f, "found {} cages but {} sums", cages, sums),
+            Self::InvalidCage(n) => write!(f, "cage {} can't add up to its sum", n),
+            Self::UnknownConstraint(kind) => write!(f, "unknown constraint {:?}", kind),
+            Self::InvalidCell(cell) => write!(f, "invalid cell {:?}", cell),
+            Self::InvalidPath(path) => write!(f, "invalid path {:?}", path),
+            Self::InvalidClue(clue) => write!(f, "invalid clue {:?}", clue),
+            Self::InvalidLayout => write!(f, "spaces outside every whole grid"),
+        }
+    }
+}
+
+impl Error for ParseGridError {}
+
+impl<const BW: usize, const BH: usize, const N: usize> FromStr for Board<BW, BH, N> {
+    type Err = ParseGridError;
+
+    /// Read the spaces in row-major order, from `.` or `0` for an empty
+    /// space and the value symbols, skipping everything else. Any spaces
+    /// left over at the end are empty. A full grid may be followed by a
+    /// second layer of `N * N` symbols giving the region of each space, for
+    /// a jigsaw grid. A killer grid ends with its cages, in the format read
+    /// by `with_cages`, which are all that's needed when there are no
+    /// givens. Any other constraints follow, each after a `;`, such as
+    /// `;thermo:r1c1-r1c2-r1c3`, `;arrow:r5c5-r6c6`, `;sandwich:r1=12`,
+    /// `;white:r1c1-r1c2`, `;compare:r1c1<r1c2` or `;antiknight`.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut numbers = vec![];
-        for c in s.chars() {
-            let c = if c == '.' { '0' } else { c };
-            if c.is_ascii_digit().not() {
-                continue;
+        if let Some((grid, constraints)) = s.split_once(';') {
+            return constraints
+                .split(';')
+                .map(str::trim)
+                .filter(|constraint| constraint.is_empty().not())
+                .try_fold(grid.parse::<Self>()?, Self::with_written_constraint);
+        }
+        if let Some(sums) = s.find('=') {
+            let cells = s[..sums]
+                .char_indices()
+                .filter(|&(_, c)| c == '.' || c.is_ascii_alphanumeric())
+                .map(|(offset, _)| offset)
+                .collect::<Vec<usize>>();
+            if cells.len() < N * N {
+                return Err(ParseGridError::CageSpaces(cells.len()));
             }
-            numbers.push(c.to_string().parse()?);
+            let cages = cells[cells.len() - N * N];
+            return s[..cages].parse::<Self>()?.with_cages(&s[cages..]);
+        }
+
+        let symbols = s
+            .chars()
+            .filter(|&c| c == '.' || c.is_ascii_alphanumeric())
+            .collect::<Vec<char>>();
+        let (values, regions) = match symbols.len() {
+            n if n <= N * N => (&symbols[..], None),
+            n if n == 2 * N * N => (&symbols[..N * N], Some(&symbols[N * N..])),
+            _ => return Err(ParseGridError::TooManySpaces(N)),
+        };
+
+        let mut grid = Self::empty();
+        for (i, &c) in values.iter().enumerate() {
+            let space = match Space::from_symbol(c) {
+                Some(Space::Occupied(v)) if usize::from(u8::from(v)) > N => None,
+                space => space,
+            };
+            grid.spaces[i] = space.ok_or(ParseGridError::InvalidSymbol(c))?;
         }
 
-        Ok(Self::from(numbers))
+        match regions {
+            Some(regions) => grid.with_regions(&regions.iter().collect::<String>()),
+            None => Ok(grid),
+        }
     }
 }
 
-impl Grid {
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Add a constraint written as `kind:details`, as it's written by
+    /// `to_line`.
+    fn with_written_constraint(self, constraint: &str) -> Result<Self, ParseGridError> {
+        let (kind, details) = constraint.split_once(':').unwrap_or((constraint, ""));
+        match kind.trim() {
+            "diagonal" => Ok(self.with_diagonals()),
+            "thermo" => self.with_thermo(details),
+            "arrow" => self.with_arrow(details),
+            "sandwich" => self.with_sandwich(details),
+            "antiknight" => Ok(self.with_anti_knight()),
+            "antiking" => Ok(self.with_anti_king()),
+            "white" => self.with_white_dot(details),
+            "black" => self.with_black_dot(details),
+            "x" => self.with_x(details),
+            "v" => self.with_v(details),
+            "compare" => self.with_comparison(details),
+            "negative" => match details.trim() {
+                "kropki" => Ok(self.with_negative_kropki()),
+                "xv" => Ok(self.with_negative_xv()),
+                _ => Err(ParseGridError::UnknownConstraint(constraint.trim().into())),
+            },
+            kind => Err(ParseGridError::UnknownConstraint(kind.into())),
+        }
+    }
+
     #[throws(Box<dyn std::error::Error>)]
     pub fn from_file(path: &str) -> Self {
         let file = File::open(path)?;
@@ -310,12 +844,63 @@ impl Grid {
 
         Self::from_str(&contents)?
     }
+
+    /// Read every puzzle in a file, one per line in the `N * N` character
+    /// format.
+    #[throws(Box<dyn std::error::Error>)]
+    pub fn all_from_file(path: &str) -> Vec<Self> {
+        let file = File::open(path)?;
+        let mut buf_reader = BufReader::new(file);
+        let mut contents = String::new();
+        buf_reader.read_to_string(&mut contents)?;
+
+        Self::all_from_str(&contents)?
+    }
+
+    /// Parse a collection of puzzles, one per line. Blank lines and lines
+    /// starting with `#` are skipped, as is anything after the first word of
+    /// a line, so rated output from the generator can be read back in.
+    #[throws(Box<dyn std::error::Error>)]
+    pub fn all_from_str(s: &str) -> Vec<Self> {
+        let mut grids = vec![];
+        for (n, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let puzzle = line.split_whitespace().next().unwrap_or_default();
+            // Only the spaces are checked here, and any constraints after
+            // them are left to `from_str`.
+            let spaces = puzzle.split(';').next().unwrap_or_default();
+            let cells = spaces.chars().count();
+            if puzzle.contains('=').not() && cells != N * N && cells != 2 * N * N {
+                throw!(format!(
+                    "line {}: expected {} cells, or {} with regions, found {}",
+                    n + 1,
+                    N * N,
+                    2 * N * N,
+                    cells
+                ));
+            }
+            if let Some(c) = spaces
+                .chars()
+                .find(|&c| ".=,".contains(c).not() && c.is_ascii_alphanumeric().not())
+            {
+                throw!(format!("line {}: unexpected character {:?}", n + 1, c));
+            }
+            grids.push(Self::from_str(puzzle)?);
+        }
+
+        grids
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use test_case::test_case;
 
+    use crate::solver::Solvers;
+
     use super::*;
 
     #[test_case(Grid::empty(), false)]
@@ -331,7 +916,163 @@ mod tests {
     }
 
     #[test]
-    #[throws(ParseIntError)]
+    fn other_sizes_solve() {
+        assert!(Grid4::new_solved().is_solved());
+        assert!(Grid6::new_solved().is_solved());
+        assert!(Grid8::new_solved().is_solved());
+        assert!(Grid16::new_solved().is_solved());
+        assert!(Grid25::new_solved().is_solved());
+    }
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn four_by_four_round_trip() {
+        #[rustfmt::skip]
+        let expected = r"
+┌─────────────┐
+│ 1  2 ┆ 3  4 │
+│ 3  4 ┆ 1  2 │
+│┄┄┄┄┄┄┼┄┄┄┄┄┄│
+│ 2  1 ┆ 4  3 │
+│ 4  3 ┆ .  . │
+└─────────────┘
+";
+        let expected = expected.trim_start();
+        let grid: Grid4 = expected.parse()?;
+        assert_eq!(grid.clues(), 14);
+        assert_eq!(format!("{}", grid), expected);
+    }
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn six_by_six_round_trip() {
+        #[rustfmt::skip]
+        let expected = r"
+┌───────────────────┐
+│ 1  2  3 ┆ 4  5  6 │
+│ 4  5  6 ┆ 1  2  3 │
+│┄┄┄┄┄┄┄┄┄┼┄┄┄┄┄┄┄┄┄│
+│ 2  3  1 ┆ 5  6  4 │
+│ 5  6  4 ┆ 2  3  1 │
+│┄┄┄┄┄┄┄┄┄┼┄┄┄┄┄┄┄┄┄│
+│ 3  1  2 ┆ 6  4  5 │
+│ 6  4  5 ┆ 3  .  . │
+└───────────────────┘
+";
+        let expected = expected.trim_start();
+        let grid: Grid6 = expected.parse()?;
+        assert_eq!(grid.clues(), 34);
+        assert_eq!(format!("{}", grid), expected);
+        assert!(grid.solve().unwrap().is_solved());
+    }
+
+    const JIGSAW: &str = concat!(
+        "...45.......8.........3..5624..........71...5....8.2..3....8.9........6.7......1.",
+        "111122233111222333112223333444455666444555666447555666777588999777888999778888999",
+    );
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn jigsaw_solves() {
+        let grid: Grid = JIGSAW.parse()?;
+        assert!(grid.is_jigsaw());
+        assert_eq!(grid.to_line(), JIGSAW);
+
+        let solution = grid.clone().propagation_solve().unwrap();
+        assert!(solution.is_solved());
+        assert!(solution.is_jigsaw());
+        assert_eq!(grid.count_solutions(2), 1);
+        assert!(Grid::new_solved()
+            .with_regions(&JIGSAW[81..])?
+            .is_solved()
+            .not());
+    }
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn layouts_are_dropped_with_their_grids() {
+        let grid = Grid4::empty().with_regions("1112122233343444")?;
+        assert!(grid.is_jigsaw());
+        let layout = Arc::downgrade(&grid.layout);
+        let same = Grid4::empty().with_regions("1112122233343444")?;
+        assert!(Arc::ptr_eq(&grid.layout, &same.layout));
+
+        drop((grid, same));
+        assert!(layout.upgrade().is_none());
+    }
+
+    #[test]
+    fn diagonals_must_hold_every_value() {
+        let solution = Grid::new_solved();
+        assert!(solution.with_diagonals().is_solved().not());
+
+        let diagonal = Grid::empty().with_diagonals().solve().unwrap();
+        assert!(diagonal.is_diagonal());
+        assert!(diagonal.is_solved());
+        for solver in Solvers::default().iter() {
+            let mut puzzle = diagonal.clone();
+            puzzle.spaces[0] = Space::Empty;
+            puzzle.spaces[10] = Space::Empty;
+            assert_eq!(solver.solve(puzzle), Some(diagonal.clone()));
+        }
+    }
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn diagonals_round_trip() {
+        let grid = Grid4::empty().with_diagonals().with_anti_king();
+        let line = format!("{};diagonal;antiking", ".".repeat(16));
+        assert_eq!(grid.to_line(), line);
+
+        let read = line.parse::<Grid4>()?;
+        assert!(read.is_diagonal());
+        assert_eq!(read, grid);
+    }
+
+    #[test]
+    fn jigsaw_renders_region_borders() {
+        let grid: Grid = JIGSAW.parse().unwrap();
+        let rendered = format!("{}", grid);
+
+        assert!(rendered.contains("│ .  .   .   4 ┃ 5   .   . ┃ .  . │"));
+        assert!(rendered.contains("│━━━━━━┻━━━━━━━┳━━━┻━━━┳━━━━━━━━━━│"));
+    }
+
+    #[test_case(&"1".repeat(81), ParseGridError::InvalidRegion('1') ; "too large")]
+    #[test_case(
+        "111222333111222333112223333444555666444555666444555666777888999777888999777888999",
+        ParseGridError::InvalidRegion('1') ;
+        "wrong size"
+    )]
+    #[test_case(
+        "211222333111122333111222333444555666444555666444555666777888999777888999777888999",
+        ParseGridError::InvalidRegion('2') ;
+        "disconnected"
+    )]
+    #[test_case("123", ParseGridError::RegionSpaces(3) ; "too few spaces")]
+    fn jigsaw_rejects(regions: &str, expected: ParseGridError) {
+        assert_eq!(Grid::empty().with_regions(regions).err(), Some(expected));
+    }
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn sixteen_by_sixteen_uses_letters() {
+        let solution = Grid16::new_solved();
+        let line = solution.to_line();
+        assert!(line.contains('G'));
+
+        let grid: Grid16 = line.to_lowercase().parse()?;
+        assert_eq!(grid, solution);
+    }
+
+    #[test_case("5" ; "symbol too large")]
+    #[test_case("12341234123412341" ; "too many spaces")]
+    fn four_by_four_rejects(s: &str) {
+        assert!(s.parse::<Grid4>().is_err());
+    }
+
+    #[test]
+    #[throws(ParseGridError)]
     fn round_trip() {
         #[rustfmt::skip]
         let expected = r"
@@ -355,7 +1096,7 @@ mod tests {
     }
 
     #[test]
-    #[throws(ParseIntError)]
+    #[throws(ParseGridError)]
     fn round_trip_empty() {
         #[rustfmt::skip]
         let expected = r"
@@ -380,7 +1121,7 @@ mod tests {
     }
 
     #[test]
-    #[throws(ParseIntError)]
+    #[throws(ParseGridError)]
     fn zeros_as_empty() {
         #[rustfmt::skip]
         let expected = r"
@@ -402,4 +1143,43 @@ mod tests {
         let grid: Grid = expected.parse()?;
         assert_eq!(grid, Grid::empty());
     }
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn line_round_trip() {
+        let expected =
+            "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..";
+        let grid: Grid = expected.parse()?;
+        assert_eq!(grid.clues(), 32);
+        assert_eq!(grid.to_line(), expected);
+    }
+
+    #[test]
+    #[throws(Box<dyn std::error::Error>)]
+    fn all_from_str_skips_comments_and_columns() {
+        let puzzle =
+            "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..";
+        let contents = format!("# a corpus\n{}\n\n{} 32 hidden-single\n", puzzle, puzzle);
+        let grids = Grid::all_from_str(&contents)?;
+        assert_eq!(grids.len(), 2);
+        assert!(grids.iter().all(|grid| grid.to_line() == puzzle));
+    }
+
+    #[test]
+    #[throws(Box<dyn std::error::Error>)]
+    fn all_from_str_reads_constraints_back() {
+        let grid = Grid4::empty()
+            .with_comparison("r1c1<r1c2")?
+            .with_thermo("r2c1-r3c2")?;
+        let contents = format!("{}\n{} 0\n", grid.to_line(), grid.to_line());
+        let grids = Grid4::all_from_str(&contents)?;
+        assert_eq!(grids, vec![grid.clone(), grid]);
+    }
+
+    #[test_case("..3.2.6.." ; "too short")]
+    #[test_case(&"x".repeat(81) ; "bad character")]
+    #[test_case(&format!("{};compare:r1c1?r1c2", ".".repeat(81)) ; "bad constraint")]
+    fn all_from_str_rejects_malformed_lines(contents: &str) {
+        assert!(Grid::all_from_str(contents).is_err());
+    }
 }
@@ -0,0 +1,120 @@
+use std::collections::BTreeSet;
+
+use crate::{space::Space, value::Value};
+
+use super::Board;
+
+/// How the empty spaces of a grid are constrained across all its solutions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backbone {
+    /// Empty spaces that hold the same value in every solution.
+    pub forced: Vec<((u8, u8), Value)>,
+    /// Empty spaces that differ between solutions, with every value each can
+    /// take.
+    pub choices: Vec<((u8, u8), Vec<Value>)>,
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Work out which empty spaces are forced to the same value in every
+    /// solution, and which have a choice. Returns `None` if there are no
+    /// solutions at all.
+    pub fn backbone(&self) -> Option<Backbone> {
+        let first = self.solutions(1).into_iter().next()?;
+
+        // Every solution found along the way witnesses one possible value
+        // for each space, which saves searching for it separately.
+        let mut possible = vec![BTreeSet::new(); N * N];
+        let witness = |solution: &Self, possible: &mut Vec<BTreeSet<Value>>| {
+            for (i, space) in solution.spaces.iter().enumerate() {
+                if let Space::Occupied(value) = space {
+                    possible[i].insert(*value);
+                }
+            }
+        };
+        witness(&first, &mut possible);
+
+        for i in 0..N * N {
+            if self.spaces[i].is_occupied() {
+                continue;
+            }
+            let (x, y) = Self::position(i);
+            for value in self.candidates(x, y) {
+                if possible[i].contains(&value) {
+                    continue;
+                }
+                let mut attempt = self.clone();
+                attempt.set(x, y, value);
+                if let Some(solution) = attempt.solutions(1).first() {
+                    witness(solution, &mut possible);
+                }
+            }
+        }
+
+        let mut backbone = Backbone {
+            forced: vec![],
+            choices: vec![],
+        };
+        for (i, values) in possible.iter().enumerate() {
+            if self.spaces[i].is_occupied() {
+                continue;
+            }
+            let values = values.iter().cloned().collect::<Vec<Value>>();
+            match values.as_slice() {
+                [value] => backbone.forced.push((Self::position(i), *value)),
+                _ => backbone.choices.push((Self::position(i), values)),
+            }
+        }
+
+        Some(backbone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+
+    use super::*;
+
+    #[test]
+    fn unique_grid_is_all_forced() {
+        let grid: Grid =
+            "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+                .parse()
+                .unwrap();
+        let backbone = grid.backbone().unwrap();
+
+        assert_eq!(backbone.forced.len(), 81 - grid.clues());
+        assert!(backbone.choices.is_empty());
+    }
+
+    #[test]
+    fn unsolvable_grid_has_no_backbone() {
+        let grid: Grid = "11".parse().unwrap();
+
+        assert_eq!(grid.backbone(), None);
+    }
+
+    #[test]
+    fn swappable_rectangle_is_a_choice() {
+        // The solved grid from the round trip tests, with a rectangle of 3s
+        // and 7s removed. Those can be swapped, so they're choices, while the
+        // 7 removed from the bottom right corner is forced.
+        let grid: Grid =
+            "..5416892416892375892375416..456192856192873492873456115364728964728915328915364."
+                .parse()
+                .unwrap();
+        let backbone = grid.backbone().unwrap();
+
+        assert_eq!(backbone.forced, vec![((8, 8), Value::from(7))]);
+        let either = vec![Value::from(3), Value::from(7)];
+        assert_eq!(
+            backbone.choices,
+            vec![
+                ((0, 0), either.clone()),
+                ((1, 0), either.clone()),
+                ((0, 3), either.clone()),
+                ((1, 3), either),
+            ]
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use crate::technique::Technique;
+
+use super::Board;
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// The fewest spaces that, once given their solution values, let naked
+    /// and hidden singles solve the rest of the grid. Returns `None` unless
+    /// the grid has exactly one solution.
+    pub fn backdoor(&self) -> Option<Vec<(u8, u8)>> {
+        self.backdoor_with(&[Technique::NakedSingle, Technique::HiddenSingle])
+    }
+
+    /// The fewest spaces that, once given their solution values, let these
+    /// techniques solve the rest of the grid.
+    pub fn backdoor_with(&self, techniques: &[Technique]) -> Option<Vec<(u8, u8)>> {
+        let solution = match self.solutions(2).as_slice() {
+            [solution] => solution.clone(),
+            _ => return None,
+        };
+
+        (0..=N * N).find_map(|size| {
+            let mut chosen = vec![];
+            if self.find_backdoor(&solution, techniques, 0, size, &mut chosen) {
+                Some(chosen.into_iter().map(Self::position).collect())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Search for `size` more spaces at or after `start` that open up the
+    /// grid, adding them to `chosen`. Only spaces the techniques couldn't
+    /// fill are worth giving, since any other would leave a smaller backdoor
+    /// without it, and taking them in order avoids trying each set twice.
+    fn find_backdoor(
+        &self,
+        solution: &Self,
+        techniques: &[Technique],
+        start: usize,
+        size: usize,
+        chosen: &mut Vec<usize>,
+    ) -> bool {
+        let stalled = self.logical_solve_with(techniques).grid;
+        if stalled.is_complete() {
+            return true;
+        }
+        if size == 0 {
+            return false;
+        }
+
+        for i in start..N * N {
+            if stalled.spaces[i].is_occupied() {
+                continue;
+            }
+            let mut next = stalled.clone();
+            next.spaces[i] = solution.spaces[i];
+            chosen.push(i);
+            if next.find_backdoor(solution, techniques, i + 1, size - 1, chosen) {
+                return true;
+            }
+            chosen.pop();
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use fehler::throws;
+    use rand::prelude::*;
+    use strum::IntoEnumIterator;
+
+    use crate::grid::Grid;
+
+    use super::*;
+
+    #[test]
+    #[throws(Box<dyn std::error::Error>)]
+    fn singles_need_no_backdoor() {
+        let grid = Grid::from_file("resources/test.sud")?;
+
+        assert_eq!(grid.backdoor(), Some(vec![]));
+    }
+
+    #[test]
+    fn backdoor_opens_up_a_harder_puzzle() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let puzzle = Grid::generate_requiring(Technique::NakedPair, &mut rng)
+            .unwrap()
+            .puzzle;
+        let solution = puzzle.clone().solve().unwrap();
+
+        let backdoor = puzzle.backdoor().unwrap();
+        assert!(backdoor.is_empty().not());
+        let mut opened = puzzle.clone();
+        for &(x, y) in backdoor.iter() {
+            let i = Grid::index(x, y);
+            opened.spaces[i] = solution.spaces[i];
+        }
+        let singles = [Technique::NakedSingle, Technique::HiddenSingle];
+        assert!(opened.logical_solve_with(&singles).is_solved());
+
+        let techniques = Technique::iter().collect::<Vec<Technique>>();
+        assert_eq!(puzzle.backdoor_with(&techniques), Some(vec![]));
+    }
+
+    #[test]
+    fn ambiguous_grid_has_no_backdoor() {
+        assert_eq!(Grid::empty().backdoor(), None);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{space::Space, value::Value};
+
+use super::{
+    constraint::{Constraint, Propagate},
+    Board,
+};
+
+/// A move a chess piece can make, as steps across and down.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Move {
+    Knight,
+    King,
+}
+
+impl Move {
+    fn steps(self) -> &'static [(isize, isize)] {
+        match self {
+            Self::Knight => &[
+                (1, 2),
+                (2, 1),
+                (2, -1),
+                (1, -2),
+                (-1, -2),
+                (-2, -1),
+                (-2, 1),
+                (-1, 2),
+            ],
+            Self::King => &[
+                (1, 0),
+                (1, 1),
+                (0, 1),
+                (-1, 1),
+                (-1, 0),
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+            ],
+        }
+    }
+}
+
+/// A rule over the whole grid that no two spaces a chess piece's move apart
+/// hold the same value.
+struct AntiMove {
+    piece: Move,
+    spaces: Vec<usize>,
+    /// For each space, the spaces a move away from it.
+    reach: Vec<Vec<usize>>,
+}
+
+impl AntiMove {
+    fn new(piece: Move, size: usize) -> Self {
+        let inside = |n: isize| (0..size as isize).contains(&n);
+        let reach = (0..size * size)
+            .map(|i| {
+                let (x, y) = ((i % size) as isize, (i / size) as isize);
+                piece
+                    .steps()
+                    .iter()
+                    .map(|&(across, down)| (x + across, y + down))
+                    .filter(|&(x, y)| inside(x) && inside(y))
+                    .map(|(x, y)| x as usize + y as usize * size)
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            piece,
+            spaces: (0..size * size).collect(),
+            reach,
+        }
+    }
+
+    /// Rule the value of space `i` out of every space a move away, once
+    /// it's down to its last candidate.
+    fn settle(&self, grid: &mut dyn Propagate, i: usize) -> bool {
+        match grid.candidates(i).single() {
+            Some(last) => self.reach[i].iter().all(|&p| grid.eliminate(p, last)),
+            None => true,
+        }
+    }
+}
+
+impl Constraint for AntiMove {
+    fn spaces(&self) -> &[usize] {
+        &self.spaces
+    }
+
+    fn prune(&self, grid: &mut dyn Propagate, removed: Option<(usize, Value)>) -> bool {
+        match removed {
+            Some((i, _)) => self.settle(grid, i),
+            None => self.spaces.iter().all(|&i| self.settle(grid, i)),
+        }
+    }
+
+    fn allows(&self, spaces: &[Space], i: usize, value: Value) -> bool {
+        self.reach[i]
+            .iter()
+            .all(|&p| spaces[p] != Space::Occupied(value))
+    }
+
+    fn holds(&self, spaces: &[Space]) -> bool {
+        self.spaces.iter().all(|&i| match spaces[i] {
+            Space::Occupied(value) => self.allows(spaces, i, value),
+            Space::Empty => false,
+        })
+    }
+
+    fn to_line(&self) -> Option<String> {
+        let kind = match self.piece {
+            Move::Knight => "antiknight",
+            Move::King => "antiking",
+        };
+
+        Some(kind.into())
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Forbid the same value in two spaces a knight's move apart.
+    pub fn with_anti_knight(self) -> Self {
+        self.with_constraint(AntiMove::new(Move::Knight, N))
+    }
+
+    /// Forbid the same value in two spaces a king's move apart, which only
+    /// adds the diagonal neighbours to the usual rules.
+    pub fn with_anti_king(self) -> Self {
+        self.with_constraint(AntiMove::new(Move::King, N))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use test_case::test_case;
+
+    use crate::grid::Grid6;
+
+    #[test_case(Grid6::with_anti_knight, ";antiknight" ; "anti-knight")]
+    #[test_case(Grid6::with_anti_king, ";antiking" ; "anti-king")]
+    fn solutions_keep_clear_of_a_move(with_rule: fn(Grid6) -> Grid6, rule: &str) {
+        let grid = with_rule(Grid6::empty());
+        let line = format!("{}{}", ".".repeat(36), rule);
+        assert_eq!(grid.to_line(), line);
+        assert_eq!(line.parse::<Grid6>(), Ok(grid.clone()));
+
+        let solutions = grid.solutions(20);
+        assert!(solutions.is_empty().not());
+        assert!(solutions.iter().all(|s| s.is_solved()));
+        let solution = grid.clone().backtracking_solve().unwrap();
+        assert!(solution.is_solved());
+
+        let plain = Grid6::empty().solve().unwrap();
+        assert!(with_rule(plain).is_solved().not());
+    }
+}
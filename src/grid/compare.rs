@@ -0,0 +1,203 @@
+use std::ops::Not;
+
+use rand::prelude::*;
+
+use crate::{space::Space, value::Value};
+
+use super::{
+    constraint::{Constraint, Hint, Propagate},
+    generate::Generated,
+    lines::{bounds, keep_between, number, parse_cell},
+    neighbours, Board, ParseGridError,
+};
+
+/// A marker between two spaces next to each other across or down, pointing
+/// at the smaller value.
+struct Less {
+    /// The space with the smaller value, then the one with the larger.
+    spaces: Vec<usize>,
+    size: usize,
+}
+
+impl Less {
+    fn fits(a: Space, b: Space) -> bool {
+        match (a, b) {
+            (Space::Occupied(a), Space::Occupied(b)) => number(a) < number(b),
+            _ => true,
+        }
+    }
+}
+
+impl Constraint for Less {
+    fn spaces(&self) -> &[usize] {
+        &self.spaces
+    }
+
+    fn prune(&self, grid: &mut dyn Propagate, _removed: Option<(usize, Value)>) -> bool {
+        let (smaller, larger) = (self.spaces[0], self.spaces[1]);
+        let (_, highest) = bounds(grid.candidates(larger));
+        if highest < 2 || keep_between(grid, smaller, 1, highest - 1).not() {
+            return false;
+        }
+        let (lowest, _) = bounds(grid.candidates(smaller));
+
+        keep_between(grid, larger, lowest + 1, self.size)
+    }
+
+    fn allows(&self, spaces: &[Space], i: usize, value: Value) -> bool {
+        let (smaller, larger) = (self.spaces[0], self.spaces[1]);
+        if i == smaller {
+            Self::fits(Space::Occupied(value), spaces[larger])
+        } else {
+            Self::fits(spaces[smaller], Space::Occupied(value))
+        }
+    }
+
+    fn holds(&self, spaces: &[Space]) -> bool {
+        let (smaller, larger) = (spaces[self.spaces[0]], spaces[self.spaces[1]]);
+
+        smaller != Space::Empty && larger != Space::Empty && Self::fits(smaller, larger)
+    }
+
+    fn hints(&self) -> Vec<Hint> {
+        let (smaller, larger) = (self.spaces[0], self.spaces[1]);
+        let across = smaller / self.size == larger / self.size;
+        let symbol = match (across, smaller < larger) {
+            (true, true) => '<',
+            (true, false) => '>',
+            (false, true) => '^',
+            (false, false) => 'v',
+        };
+
+        vec![Hint::Mark(smaller, larger, symbol)]
+    }
+
+    fn to_line(&self) -> Option<String> {
+        let cell = |i: usize| format!("r{}c{}", i / self.size + 1, i % self.size + 1);
+        let (smaller, larger) = (self.spaces[0], self.spaces[1]);
+        let comparison = if smaller < larger {
+            format!("{}<{}", cell(smaller), cell(larger))
+        } else {
+            format!("{}>{}", cell(larger), cell(smaller))
+        };
+
+        Some(format!("compare:{}", comparison))
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Add a marker between two spaces next to each other across or down,
+    /// written like `r1c1<r1c2` or `r2c1>r3c1`, saying which value is the
+    /// larger.
+    pub fn with_comparison(self, comparison: &str) -> Result<Self, ParseGridError> {
+        let invalid = || ParseGridError::InvalidClue(comparison.trim().into());
+        let (at, marker) = comparison
+            .char_indices()
+            .find(|&(_, c)| c == '<' || c == '>')
+            .ok_or_else(invalid)?;
+        let (first, second) = (&comparison[..at], &comparison[at + 1..]);
+        let cell = |cell: &str| {
+            let cell = cell.trim();
+            parse_cell(cell, N).ok_or_else(|| ParseGridError::InvalidCell(cell.into()))
+        };
+        let (first, second) = (cell(first)?, cell(second)?);
+        if neighbours(first, N).all(|p| p != second) {
+            return Err(invalid());
+        }
+        let spaces = if marker == '<' {
+            vec![first, second]
+        } else {
+            vec![second, first]
+        };
+
+        Ok(self.with_constraint(Less { spaces, size: N }))
+    }
+
+    /// Generate a greater-than puzzle, with a marker between every two
+    /// neighbouring spaces in the same region, and as few givens as that
+    /// leaves room for, often none.
+    pub fn generate_greater_than<R: Rng + ?Sized>(rng: &mut R) -> Generated<BW, BH, N> {
+        Self::empty().generate_greater_than_like(rng)
+    }
+
+    /// Generate a greater-than puzzle with the same rules as this grid,
+    /// ignoring any of its values.
+    pub fn generate_greater_than_like<R: Rng + ?Sized>(&self, rng: &mut R) -> Generated<BW, BH, N> {
+        let plain = self.random_solution(rng);
+        let solution = (0..N * N)
+            .flat_map(|i| {
+                neighbours(i, N)
+                    .filter(move |&p| p > i)
+                    .map(move |p| (i, p))
+            })
+            .filter(|&(i, p)| self.layout.region_of[i] == self.layout.region_of[p])
+            .fold(plain.clone(), |solution, (i, p)| {
+                let spaces = if Less::fits(plain.spaces[i], plain.spaces[p]) {
+                    vec![i, p]
+                } else {
+                    vec![p, i]
+                };
+                solution.with_constraint(Less { spaces, size: N })
+            });
+        let puzzle = solution.clone().dig(0, rng);
+
+        Generated {
+            puzzle,
+            solution,
+            attempts: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fehler::throws;
+    use test_case::test_case;
+
+    use crate::grid::{Grid, Grid4};
+
+    use super::*;
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn comparisons_round_trip_and_point_at_the_smaller() {
+        let line = "................;compare:r1c1<r1c2;compare:r1c2>r2c2";
+        let grid: Grid4 = line.parse()?;
+        assert_eq!(grid.to_line(), line);
+
+        let less = |s: &Grid4, a: usize, b: usize| Less::fits(s.spaces[a], s.spaces[b]);
+        let solutions = grid.solutions(usize::MAX);
+        assert!(solutions.iter().all(|s| s.is_solved()));
+        assert!(solutions.iter().all(|s| less(s, 0, 1) && less(s, 5, 1)));
+        assert!(Grid4::empty()
+            .with_comparison("r2c2 < r1c2")?
+            .solutions(usize::MAX)
+            .iter()
+            .all(|s| less(s, 5, 1)));
+
+        let styled = grid.to_string();
+        let lines = styled.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[1], "│ . < . ┃ .   . │");
+        assert_eq!(lines[2], "│     v ┃       │");
+    }
+
+    #[test]
+    fn generate_greater_than_four_by_four() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let generated = Grid4::generate_greater_than(&mut rng);
+
+        assert!(generated.puzzle.clues() <= 2);
+        assert!(generated.puzzle.has_constraints());
+        assert_eq!(generated.puzzle.solve(), Some(generated.solution));
+    }
+
+    #[test_case("r1c1=r1c2", ParseGridError::InvalidClue("r1c1=r1c2".into()) ; "no marker")]
+    #[test_case("r1c1<r2c2", ParseGridError::InvalidClue("r1c1<r2c2".into()) ; "diagonal")]
+    #[test_case("r1c1<r1c10", ParseGridError::InvalidCell("r1c10".into()) ; "outside")]
+    fn comparisons_reject(comparison: &str, expected: ParseGridError) {
+        assert_eq!(
+            Grid::empty().with_comparison(comparison).err(),
+            Some(expected)
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use std::{ops::Not, sync::Arc};
+
+use crate::{candidates::Candidates, space::Space, value::Value};
+
+use super::{dots::Dots, killer::Cage};
+
+/// A rule a grid's values must follow. Every row, column and region of a grid
+/// holding each value once is one of these, and variants add their own, such
+/// as killer cages, or any passed to `Board::with_constraint`.
+///
+/// Spaces are given by their index, in row-major order.
+pub trait Constraint: Send + Sync {
+    /// The spaces the rule covers. It's asked to prune again whenever one of
+    /// them loses a candidate.
+    fn spaces(&self) -> &[usize];
+
+    /// Rule out the candidates that can't follow the rule, returning false if
+    /// it can't be followed at all. `removed` is the candidate just ruled out
+    /// of one of the rule's spaces, or `None` to look at the rule afresh.
+    fn prune(&self, grid: &mut dyn Propagate, removed: Option<(usize, Value)>) -> bool;
+
+    /// Whether the rule allows `value` in space `i`, given the values placed
+    /// in `spaces` so far.
+    fn allows(&self, spaces: &[Space], i: usize, value: Value) -> bool;
+
+    /// Whether the values of a complete grid follow the rule.
+    fn holds(&self, spaces: &[Space]) -> bool;
+
+    /// How to show the rule when the grid is drawn.
+    fn hints(&self) -> Vec<Hint> {
+        vec![]
+    }
+
+    /// The rule as it's written after a grid's line, as `kind:details`, for
+    /// rules with a way of being written down.
+    fn to_line(&self) -> Option<String> {
+        None
+    }
+}
+
+/// The candidates of a grid part way through being solved, as a constraint
+/// sees them while it prunes.
+pub trait Propagate {
+    fn candidates(&self, i: usize) -> Candidates;
+
+    /// Rule `value` out of space `i`, returning false if that leads to a
+    /// contradiction.
+    fn eliminate(&mut self, i: usize, value: Value) -> bool;
+
+    /// Rule out every value but `value` from space `i`, returning false if
+    /// that leads to a contradiction.
+    fn assign(&mut self, i: usize, value: Value) -> bool {
+        let others = self
+            .candidates(i)
+            .difference(Some(value).into_iter().collect());
+        others.iter().all(|other| self.eliminate(i, other))
+    }
+}
+
+/// A way of marking a constraint on a drawn grid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Hint {
+    /// Tint the background of these spaces.
+    Shade(Vec<usize>),
+    /// Draw a dashed outline around these spaces, labelled in the corner of
+    /// the first of them.
+    Outline(Vec<usize>, String),
+    /// Draw a circle around a space's value.
+    Circle(usize),
+    /// Join these spaces up in order with a line.
+    Line(Vec<usize>),
+    /// Join these spaces up in order with a line that ends in an arrowhead.
+    Arrow(Vec<usize>),
+    /// Write a clue outside the grid, to the left of a row.
+    RowClue(usize, String),
+    /// Write a clue outside the grid, above a column.
+    ColumnClue(usize, String),
+    /// Draw a mark in the gap between two spaces next to each other across
+    /// or down.
+    Mark(usize, usize, char),
+}
+
+/// Spaces that must all hold different values, such as a row, a column, a
+/// region or a diagonal.
+pub(super) struct Unique {
+    spaces: Vec<usize>,
+    /// Whether to tint the spaces, to pick out a unit that isn't otherwise
+    /// drawn, like a diagonal.
+    shaded: bool,
+}
+
+impl Unique {
+    pub(super) fn new(spaces: Vec<usize>, shaded: bool) -> Self {
+        Self { spaces, shaded }
+    }
+
+    /// Rule the value of space `i` out of every other space, once it's down
+    /// to its last candidate.
+    fn settle(&self, grid: &mut dyn Propagate, i: usize) -> bool {
+        let last = match grid.candidates(i).single() {
+            Some(last) => last,
+            None => return true,
+        };
+        let others = self.spaces.iter().filter(|&&p| p != i);
+
+        others.cloned().all(|p| grid.eliminate(p, last))
+    }
+
+    /// Put `value` in the only space it has left to go, if there's only one.
+    fn place(&self, grid: &mut dyn Propagate, value: Value) -> bool {
+        let mut places = self
+            .spaces
+            .iter()
+            .cloned()
+            .filter(|&p| grid.candidates(p).contains(value));
+        match (places.next(), places.next()) {
+            (None, _) => false,
+            (Some(p), None) => grid.assign(p, value),
+            _ => true,
+        }
+    }
+}
+
+impl Constraint for Unique {
+    fn spaces(&self) -> &[usize] {
+        &self.spaces
+    }
+
+    /// A space down to its last candidate takes it from every other space,
+    /// and a value with only one place left to go must go there.
+    fn prune(&self, grid: &mut dyn Propagate, removed: Option<(usize, Value)>) -> bool {
+        if let Some((i, value)) = removed {
+            return self.settle(grid, i) && self.place(grid, value);
+        }
+
+        let values = self
+            .spaces
+            .iter()
+            .fold(Candidates::none(), |acc, &p| acc.union(grid.candidates(p)));
+        values.len() >= self.spaces.len()
+            && self.spaces.iter().all(|&i| self.settle(grid, i))
+            && values.iter().all(|value| self.place(grid, value))
+    }
+
+    fn allows(&self, spaces: &[Space], i: usize, value: Value) -> bool {
+        self.spaces
+            .iter()
+            .all(|&p| p == i || spaces[p] != Space::Occupied(value))
+    }
+
+    fn holds(&self, spaces: &[Space]) -> bool {
+        let mut seen = Candidates::none();
+        for &i in self.spaces.iter() {
+            match spaces[i] {
+                Space::Occupied(value) if seen.contains(value).not() => seen.insert(value),
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    fn hints(&self) -> Vec<Hint> {
+        if self.shaded {
+            vec![Hint::Shade(self.spaces.clone())]
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// The constraints a grid has beyond the units of its layout: a killer grid's
+/// cages, then its dots, then any others it's been given, in the order they
+/// were added.
+#[derive(Clone, Default)]
+pub(super) struct Rules {
+    pub(super) cages: Vec<Cage>,
+    pub(super) dots: Dots,
+    others: Vec<Arc<dyn Constraint>>,
+    /// For each space, the constraints covering it, by their position in
+    /// `iter`.
+    of: Vec<Vec<usize>>,
+}
+
+impl Rules {
+    /// The same rules with `cages` in place of any there were, for a grid of
+    /// `spaces` spaces.
+    pub(super) fn with_cages(&self, cages: Vec<Cage>, spaces: usize) -> Self {
+        Self {
+            cages,
+            dots: self.dots.clone(),
+            others: self.others.clone(),
+            of: vec![],
+        }
+        .indexed(spaces)
+    }
+
+    /// The same rules with `dots` in place of any there were, for a grid of
+    /// `spaces` spaces.
+    pub(super) fn with_dots(&self, dots: Dots, spaces: usize) -> Self {
+        Self {
+            cages: self.cages.clone(),
+            dots,
+            others: self.others.clone(),
+            of: vec![],
+        }
+        .indexed(spaces)
+    }
+
+    /// The same rules with `constraint` added, for a grid of `spaces` spaces.
+    pub(super) fn with(&self, constraint: Arc<dyn Constraint>, spaces: usize) -> Self {
+        let mut others = self.others.clone();
+        others.push(constraint);
+
+        Self {
+            cages: self.cages.clone(),
+            dots: self.dots.clone(),
+            others,
+            of: vec![],
+        }
+        .indexed(spaces)
+    }
+
+    fn indexed(mut self, spaces: usize) -> Self {
+        let mut of = vec![vec![]; spaces];
+        for (r, rule) in self.iter().enumerate() {
+            for &i in rule.spaces() {
+                of[i].push(r);
+            }
+        }
+        self.of = of;
+
+        self
+    }
+
+    pub(super) fn get(&self, r: usize) -> &dyn Constraint {
+        if let Some(cage) = self.cages.get(r) {
+            return cage;
+        }
+        let r = r - self.cages.len();
+
+        match self.dots.get(r) {
+            Some(dot) => dot,
+            None => &*self.others[r - self.dots.len()],
+        }
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = &dyn Constraint> {
+        let cages = self.cages.iter().map(|cage| cage as &dyn Constraint);
+        cages
+            .chain(self.dots.iter())
+            .chain(self.others.iter().map(|other| &**other))
+    }
+
+    /// The constraints covering space `i`, by their position in `iter`.
+    pub(super) fn of(&self, i: usize) -> &[usize] {
+        self.of.get(i).map_or(&[], Vec::as_slice)
+    }
+}
+
+impl PartialEq for Rules {
+    /// Cages are compared by their spaces and sums, dots by how they're
+    /// written down, and other constraints by how they're written down too,
+    /// or if they can't be, by whether they're the same one.
+    fn eq(&self, other: &Self) -> bool {
+        let same = |a: &Arc<dyn Constraint>, b: &Arc<dyn Constraint>| match a.to_line() {
+            Some(line) => b.to_line() == Some(line),
+            None => Arc::ptr_eq(a, b),
+        };
+
+        self.cages == other.cages
+            && self.dots == other.dots
+            && self.others.len() == other.others.len()
+            && self
+                .others
+                .iter()
+                .zip(other.others.iter())
+                .all(|(a, b)| same(a, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid4;
+
+    use super::*;
+
+    /// A rule of the test's own: the spaces must hold even values.
+    struct Even(Vec<usize>);
+
+    fn is_even(value: Value) -> bool {
+        u8::from(value) % 2 == 0
+    }
+
+    impl Constraint for Even {
+        fn spaces(&self) -> &[usize] {
+            &self.0
+        }
+
+        fn prune(&self, grid: &mut dyn Propagate, _removed: Option<(usize, Value)>) -> bool {
+            self.0.iter().all(|&i| {
+                let odd = grid.candidates(i).iter().filter(|&v| is_even(v).not());
+                odd.collect::<Vec<Value>>()
+                    .into_iter()
+                    .all(|v| grid.eliminate(i, v))
+            })
+        }
+
+        fn allows(&self, _spaces: &[Space], _i: usize, value: Value) -> bool {
+            is_even(value)
+        }
+
+        fn holds(&self, spaces: &[Space]) -> bool {
+            self.0
+                .iter()
+                .all(|&i| matches!(spaces[i], Space::Occupied(v) if is_even(v)))
+        }
+    }
+
+    #[test]
+    fn solvers_follow_added_constraints() {
+        let grid = Grid4::empty().with_constraint(Even(vec![0, 5, 10]));
+        let even = |solution: &Grid4| {
+            [0, 5, 10].iter().all(|&i| match solution.spaces[i] {
+                Space::Occupied(v) => is_even(v),
+                Space::Empty => false,
+            })
+        };
+
+        let solutions = grid.solutions(usize::MAX);
+        assert!(solutions.len() < Grid4::empty().count_solutions(usize::MAX));
+        assert!(solutions.iter().all(|s| s.is_solved() && even(s)));
+        let solution = grid.clone().backtracking_solve().unwrap();
+        assert!(solution.is_solved() && even(&solution));
+
+        let unconstrained = Grid4::empty().solutions(usize::MAX);
+        let broken = unconstrained.into_iter().find(|s| even(s).not()).unwrap();
+        assert!(broken
+            .with_constraint(Even(vec![0, 5, 10]))
+            .is_solved()
+            .not());
+    }
+}
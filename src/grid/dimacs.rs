@@ -0,0 +1,221 @@
+use std::{collections::HashSet, fmt::Write, ops::Not};
+
+use fehler::{throw, throws};
+
+use crate::{space::Space, value::Value};
+
+use super::{Board, Constraint};
+
+/// The variable that is true when space `i` of a grid with `size` values
+/// holds `value`, numbered from 1 as `size * size * y + size * x + value`.
+fn variable(i: usize, value: Value, size: usize) -> i32 {
+    (i * size) as i32 + i32::from(u8::from(value))
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Encode the rules of sudoku and this grid's givens as a SAT problem in
+    /// DIMACS CNF format. Only the units are encoded, so killer cages and any
+    /// other constraints are left out.
+    pub fn to_dimacs(&self) -> String {
+        let variable = |i, value| variable(i, value, N);
+        let values = Value::all(N).collect::<Vec<Value>>();
+        let mut clauses: Vec<Vec<i32>> = vec![];
+
+        for i in 0..N * N {
+            // Every space holds at least one value...
+            clauses.push(values.iter().map(|&v| variable(i, v)).collect());
+            // ...and at most one.
+            for (n, &a) in values.iter().enumerate() {
+                for &b in values[n + 1..].iter() {
+                    clauses.push(vec![-variable(i, a), -variable(i, b)]);
+                }
+            }
+        }
+
+        for unit in self.layout.units.iter().map(Constraint::spaces) {
+            for &value in values.iter() {
+                // Every unit holds each value at least once...
+                clauses.push(unit.iter().map(|&i| variable(i, value)).collect());
+                // ...and at most once.
+                for (n, &a) in unit.iter().enumerate() {
+                    for &b in unit[n + 1..].iter() {
+                        clauses.push(vec![-variable(a, value), -variable(b, value)]);
+                    }
+                }
+            }
+        }
+
+        for (i, space) in self.spaces.iter().enumerate() {
+            if let Space::Occupied(value) = space {
+                clauses.push(vec![variable(i, *value)]);
+            }
+        }
+
+        let mut dimacs = String::new();
+        writeln!(dimacs, "c sudoku: {}", self.to_line()).unwrap();
+        writeln!(
+            dimacs,
+            "c variable {} * y + {} * x + v is true when (x, y) holds v",
+            N * N,
+            N
+        )
+        .unwrap();
+        writeln!(dimacs, "p cnf {} {}", N * N * N, clauses.len()).unwrap();
+        for clause in clauses {
+            for literal in clause {
+                write!(dimacs, "{} ", literal).unwrap();
+            }
+            writeln!(dimacs, "0").unwrap();
+        }
+
+        dimacs
+    }
+
+    /// Read back the assignment a SAT solver found for the problem from
+    /// `to_dimacs`, as a solution to this grid, keeping its regions,
+    /// diagonals and other rules. Both bare lists of literals and the `s`/`v`
+    /// lines of the SAT competition output format are accepted.
+    #[throws(Box<dyn std::error::Error>)]
+    pub fn with_sat_model(&self, model: &str) -> Self {
+        let mut grid = self.cleared();
+        let mut seen = HashSet::new();
+        for line in model.lines() {
+            let line = line.trim();
+            if line.starts_with('c') {
+                continue;
+            }
+            if line.contains("UNSAT") {
+                throw!("the SAT solver found the problem unsatisfiable");
+            }
+            let line = line.trim_start_matches(&['s', 'v'][..]);
+            for word in line.split_whitespace() {
+                if word == "SAT" || word == "SATISFIABLE" {
+                    continue;
+                }
+                let literal: i32 = word.parse()?;
+                if literal <= 0 {
+                    continue;
+                }
+                if literal as usize > N * N * N {
+                    throw!(format!("variable {} is out of range", literal));
+                }
+                let i = (literal - 1) as usize / N;
+                let value = Value::from(((literal - 1) as usize % N + 1) as u8);
+                if seen.insert(i).not() {
+                    throw!(format!("space {} is assigned more than one value", i));
+                }
+                if let Space::Occupied(given) = self.spaces[i] {
+                    if given != value {
+                        throw!(format!("space {} is given {}, not {}", i, given, value));
+                    }
+                }
+                grid.spaces[i] = Space::Occupied(value);
+            }
+        }
+        if let Some(i) = (0..N * N).find(|i| seen.contains(i).not()) {
+            throw!(format!("space {} is not assigned a value", i));
+        }
+
+        grid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+
+    use super::*;
+
+    const PUZZLE: &str =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..";
+
+    /// The model a SAT solver would give for `solution`.
+    fn model(solution: &Grid) -> String {
+        let mut literals = vec![];
+        for (i, space) in solution.spaces.iter().enumerate() {
+            for value in Value::all(9) {
+                let literal = variable(i, value, 9);
+                if *space == Space::Occupied(value) {
+                    literals.push(literal);
+                } else {
+                    literals.push(-literal);
+                }
+            }
+        }
+        let literals = literals.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+
+        format!("s SATISFIABLE\nv {} 0\n", literals.join(" "))
+    }
+
+    /// Whether the literals in `model` satisfy every clause in `dimacs`.
+    fn satisfies(dimacs: &str, model: &str) -> bool {
+        let trues = model
+            .split_whitespace()
+            .filter_map(|w| w.parse::<i32>().ok())
+            .filter(|&l| l > 0)
+            .collect::<HashSet<i32>>();
+        dimacs
+            .lines()
+            .filter(|l| l.starts_with('c').not() && l.starts_with('p').not())
+            .all(|clause| {
+                clause
+                    .split_whitespace()
+                    .map(|w| w.parse::<i32>().unwrap())
+                    .take_while(|&l| l != 0)
+                    .any(|l| trues.contains(&l.abs()) == (l > 0))
+            })
+    }
+
+    #[test]
+    #[throws(Box<dyn std::error::Error>)]
+    fn solution_satisfies_encoding() {
+        let puzzle: Grid = PUZZLE.parse()?;
+        let solution = puzzle.clone().solve().unwrap();
+        let dimacs = puzzle.to_dimacs();
+
+        assert!(dimacs.contains("p cnf 729 12020\n"));
+        assert!(satisfies(&dimacs, &model(&solution)));
+        assert_eq!(puzzle.with_sat_model(&model(&solution))?, solution);
+    }
+
+    #[test]
+    #[throws(Box<dyn std::error::Error>)]
+    fn wrong_solution_violates_givens() {
+        let puzzle: Grid = PUZZLE.parse()?;
+        let other = Grid::new_solved();
+
+        assert!(satisfies(&puzzle.to_dimacs(), &model(&other)).not());
+    }
+
+    #[test]
+    #[throws(Box<dyn std::error::Error>)]
+    fn model_keeps_the_rules_of_the_grid() {
+        let template = Grid::empty().with_diagonals();
+        let solution = template.clone().solve().unwrap();
+        let imported = template.with_sat_model(&model(&solution))?;
+
+        assert_eq!(imported, solution);
+        assert!(imported.is_solved());
+    }
+
+    #[test]
+    fn unassigned_space_is_an_error() {
+        assert!(Grid::empty().with_sat_model("v 1 0\n").is_err());
+    }
+
+    #[test]
+    fn space_with_two_values_is_an_error() {
+        let solution = Grid::new_solved();
+        let other = Value::all(9)
+            .find(|&v| solution.spaces[0] != Space::Occupied(v))
+            .unwrap();
+        let model = format!("{}v {} 0\n", model(&solution), variable(0, other, 9));
+
+        assert!(Grid::empty().with_sat_model(&model).is_err());
+    }
+
+    #[test]
+    fn unsatisfiable_model_is_an_error() {
+        assert!(Grid::empty().with_sat_model("s UNSATISFIABLE\n").is_err());
+    }
+}
@@ -0,0 +1,421 @@
+use std::{ops::Not, sync::Arc};
+
+use crate::{space::Space, value::Value};
+
+use super::{
+    constraint::{Constraint, Hint, Propagate},
+    lines::{number, parse_path, path_line},
+    neighbours, Board, ParseGridError,
+};
+
+/// A mark between two neighbouring spaces, saying how their values relate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Dot {
+    /// The values are consecutive.
+    White,
+    /// One value is double the other.
+    Black,
+    /// The values add up to 10.
+    X,
+    /// The values add up to 5.
+    V,
+}
+
+impl Dot {
+    const KROPKI: [Dot; 2] = [Dot::White, Dot::Black];
+    const XV: [Dot; 2] = [Dot::X, Dot::V];
+
+    fn fits(self, a: usize, b: usize) -> bool {
+        match self {
+            Self::White => a + 1 == b || b + 1 == a,
+            Self::Black => a * 2 == b || b * 2 == a,
+            Self::X => a + b == 10,
+            Self::V => a + b == 5,
+        }
+    }
+
+    fn symbol(self) -> char {
+        match self {
+            Self::White => '○',
+            Self::Black => '●',
+            Self::X => 'X',
+            Self::V => 'V',
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::White => "white",
+            Self::Black => "black",
+            Self::X => "x",
+            Self::V => "v",
+        }
+    }
+}
+
+/// Rule out every candidate of space `p` that doesn't go with any candidate
+/// of space `i`.
+fn keep_supported(
+    grid: &mut dyn Propagate,
+    i: usize,
+    p: usize,
+    goes: impl Fn(usize, usize) -> bool,
+) -> bool {
+    let ours = grid.candidates(i);
+    let unsupported = grid
+        .candidates(p)
+        .iter()
+        .filter(|&w| ours.iter().any(|v| goes(number(v), number(w))).not())
+        .collect::<Vec<Value>>();
+
+    unsupported.into_iter().all(|w| grid.eliminate(p, w))
+}
+
+/// A dot between two neighbouring spaces, whose values must fit it.
+#[derive(Clone)]
+struct Pair {
+    dot: Dot,
+    spaces: Vec<usize>,
+    size: usize,
+}
+
+impl Constraint for Pair {
+    fn spaces(&self) -> &[usize] {
+        &self.spaces
+    }
+
+    fn prune(&self, grid: &mut dyn Propagate, _removed: Option<(usize, Value)>) -> bool {
+        let (a, b) = (self.spaces[0], self.spaces[1]);
+        let fits = |v, w| self.dot.fits(v, w);
+
+        keep_supported(grid, a, b, fits) && keep_supported(grid, b, a, fits)
+    }
+
+    fn allows(&self, spaces: &[Space], i: usize, value: Value) -> bool {
+        let other = if i == self.spaces[0] {
+            self.spaces[1]
+        } else {
+            self.spaces[0]
+        };
+
+        match spaces[other] {
+            Space::Occupied(w) => self.dot.fits(number(value), number(w)),
+            Space::Empty => true,
+        }
+    }
+
+    fn holds(&self, spaces: &[Space]) -> bool {
+        match (spaces[self.spaces[0]], spaces[self.spaces[1]]) {
+            (Space::Occupied(v), Space::Occupied(w)) => self.dot.fits(number(v), number(w)),
+            _ => false,
+        }
+    }
+
+    fn hints(&self) -> Vec<Hint> {
+        vec![Hint::Mark(
+            self.spaces[0],
+            self.spaces[1],
+            self.dot.symbol(),
+        )]
+    }
+
+    fn to_line(&self) -> Option<String> {
+        let pair = path_line(&self.spaces, self.size);
+
+        Some(format!("{}:{}", self.dot.name(), pair))
+    }
+}
+
+/// A rule over the whole grid that neighbouring spaces without one of a
+/// kind of dot between them hold values none of those dots would fit, for
+/// puzzles that show every dot there is.
+#[derive(Clone)]
+struct Unmarked {
+    dots: [Dot; 2],
+    spaces: Vec<usize>,
+    /// For each space, its neighbours with none of the dots between them.
+    partners: Vec<Vec<usize>>,
+    size: usize,
+}
+
+impl Unmarked {
+    fn new(dots: [Dot; 2], pairs: &[Pair], size: usize) -> Self {
+        let marked = pairs
+            .iter()
+            .filter(|pair| dots.contains(&pair.dot))
+            .map(|pair| (pair.spaces[0], pair.spaces[1]))
+            .collect::<Vec<(usize, usize)>>();
+        let partners = (0..size * size)
+            .map(|i| {
+                neighbours(i, size)
+                    .filter(|&p| marked.contains(&(i.min(p), i.max(p))).not())
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            dots,
+            spaces: (0..size * size).collect(),
+            partners,
+            size,
+        }
+    }
+
+    fn apart(&self, v: usize, w: usize) -> bool {
+        self.dots.iter().all(|dot| dot.fits(v, w).not())
+    }
+
+    /// Rule out the candidates of the partners of space `i` that would fit
+    /// one of the dots whatever goes in space `i`.
+    fn separate(&self, grid: &mut dyn Propagate, i: usize) -> bool {
+        self.partners[i]
+            .iter()
+            .all(|&p| keep_supported(grid, i, p, |v, w| self.apart(v, w)))
+    }
+}
+
+impl Constraint for Unmarked {
+    fn spaces(&self) -> &[usize] {
+        &self.spaces
+    }
+
+    fn prune(&self, grid: &mut dyn Propagate, removed: Option<(usize, Value)>) -> bool {
+        match removed {
+            Some((i, _)) => self.separate(grid, i),
+            None => self.spaces.iter().all(|&i| self.separate(grid, i)),
+        }
+    }
+
+    fn allows(&self, spaces: &[Space], i: usize, value: Value) -> bool {
+        self.partners[i].iter().all(|&p| match spaces[p] {
+            Space::Occupied(w) => self.apart(number(value), number(w)),
+            Space::Empty => true,
+        })
+    }
+
+    fn holds(&self, spaces: &[Space]) -> bool {
+        self.spaces.iter().all(|&i| match spaces[i] {
+            Space::Occupied(value) => self.allows(spaces, i, value),
+            Space::Empty => false,
+        })
+    }
+
+    fn to_line(&self) -> Option<String> {
+        let kind = if self.dots == Dot::KROPKI {
+            "kropki"
+        } else {
+            "xv"
+        };
+
+        Some(format!("negative:{}", kind))
+    }
+}
+
+/// A grid's dots, and any rules that they're all the dots there are, which
+/// have to know where every dot is, whichever was added first.
+#[derive(Clone, Default)]
+pub(super) struct Dots {
+    pairs: Vec<Pair>,
+    negatives: Vec<Unmarked>,
+}
+
+impl Dots {
+    fn with_pair(&self, pair: Pair) -> Self {
+        let mut pairs = self.pairs.clone();
+        pairs.push(pair);
+        let negatives = self
+            .negatives
+            .iter()
+            .map(|negative| Unmarked::new(negative.dots, &pairs, negative.size))
+            .collect();
+
+        Self { pairs, negatives }
+    }
+
+    fn with_negative(&self, dots: [Dot; 2], size: usize) -> Self {
+        let mut negatives = self.negatives.clone();
+        negatives.push(Unmarked::new(dots, &self.pairs, size));
+
+        Self {
+            pairs: self.pairs.clone(),
+            negatives,
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.pairs.len() + self.negatives.len()
+    }
+
+    pub(super) fn get(&self, r: usize) -> Option<&dyn Constraint> {
+        match self.pairs.get(r) {
+            Some(pair) => Some(pair),
+            None => self
+                .negatives
+                .get(r - self.pairs.len())
+                .map(|negative| negative as &dyn Constraint),
+        }
+    }
+
+    /// The dots, then the rules that they're all there are.
+    pub(super) fn iter(&self) -> impl Iterator<Item = &dyn Constraint> {
+        let pairs = self.pairs.iter().map(|pair| pair as &dyn Constraint);
+        pairs.chain(self.negatives.iter().map(|n| n as &dyn Constraint))
+    }
+}
+
+impl PartialEq for Dots {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter()
+            .map(Constraint::to_line)
+            .eq(other.iter().map(Constraint::to_line))
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Add a white dot between two spaces next to each other across or
+    /// down, written like `r1c1-r1c2`, whose values must be consecutive.
+    pub fn with_white_dot(self, pair: &str) -> Result<Self, ParseGridError> {
+        self.with_dot(Dot::White, pair)
+    }
+
+    /// Add a black dot between two spaces, written as for `with_white_dot`,
+    /// where one value must be double the other.
+    pub fn with_black_dot(self, pair: &str) -> Result<Self, ParseGridError> {
+        self.with_dot(Dot::Black, pair)
+    }
+
+    /// Add an X between two spaces, written as for `with_white_dot`, whose
+    /// values must add up to 10.
+    pub fn with_x(self, pair: &str) -> Result<Self, ParseGridError> {
+        self.with_dot(Dot::X, pair)
+    }
+
+    /// Add a V between two spaces, written as for `with_white_dot`, whose
+    /// values must add up to 5.
+    pub fn with_v(self, pair: &str) -> Result<Self, ParseGridError> {
+        self.with_dot(Dot::V, pair)
+    }
+
+    fn with_dot(self, dot: Dot, pair: &str) -> Result<Self, ParseGridError> {
+        let mut spaces = parse_path(pair, N)?;
+        spaces.sort_unstable();
+        if spaces.len() != 2 || neighbours(spaces[0], N).all(|p| p != spaces[1]) {
+            return Err(ParseGridError::InvalidPath(pair.trim().into()));
+        }
+
+        let dots = self.rules.dots.with_pair(Pair {
+            dot,
+            spaces,
+            size: N,
+        });
+
+        Ok(self.with_dots(dots))
+    }
+
+    fn with_dots(mut self, dots: Dots) -> Self {
+        self.rules = Arc::new(self.rules.with_dots(dots, N * N));
+
+        self
+    }
+
+    /// Rule out consecutive values, or one double the other, between
+    /// neighbouring spaces without a white or black dot, as when every dot
+    /// is shown.
+    pub fn with_negative_kropki(self) -> Self {
+        self.with_negative(Dot::KROPKI)
+    }
+
+    /// Rule out values adding up to 10 or 5 between neighbouring spaces
+    /// without an X or a V, as for `with_negative_kropki`.
+    pub fn with_negative_xv(self) -> Self {
+        self.with_negative(Dot::XV)
+    }
+
+    fn with_negative(self, dots: [Dot; 2]) -> Self {
+        let dots = self.rules.dots.with_negative(dots, N);
+
+        self.with_dots(dots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use crate::grid::{Grid, Grid4};
+
+    use super::*;
+
+    /// Every white and black dot of a solution, which only it follows once
+    /// the missing dots count too.
+    const KROPKI: &str = concat!(
+        "................",
+        ";white:r1c2-r1c3;white:r1c2-r2c2;white:r1c3-r2c3",
+        ";white:r2c2-r2c3;white:r3c1-r4c1;white:r3c4-r4c4",
+        ";black:r1c3-r1c4;black:r2c1-r2c2;black:r2c1-r3c1",
+        ";black:r2c2-r3c2;black:r3c1-r3c2;black:r4c3-r4c4",
+    );
+
+    /// Every V of a solution.
+    const XV: &str = concat!(
+        "................",
+        ";v:r1c2-r1c3;v:r2c1-r3c1;v:r2c2-r2c3;v:r2c2-r3c2",
+        ";v:r2c3-r3c3;v:r2c4-r3c4;v:r3c2-r3c3;v:r4c2-r4c3",
+    );
+
+    #[test_case(KROPKI, ";negative:kropki", 10, 1 ; "kropki")]
+    #[test_case(XV, ";negative:xv", 8, 8 ; "xv")]
+    fn dots_round_trip_and_solve(dots: &str, negative: &str, shown: usize, all: usize) {
+        let line = format!("{}{}", dots, negative);
+        let grid = line.parse::<Grid4>().unwrap();
+        assert_eq!(grid.to_line(), line);
+
+        let solutions = grid.solutions(usize::MAX);
+        assert_eq!(solutions.len(), all);
+        assert!(solutions.iter().all(|s| s.is_solved()));
+        let solution = grid.clone().backtracking_solve().unwrap();
+        assert!(solutions.contains(&solution));
+
+        let dotted = dots.parse::<Grid4>().unwrap().solutions(usize::MAX);
+        assert_eq!(dotted.len(), shown);
+        assert!(solutions
+            .iter()
+            .all(|s| dotted.iter().any(|d| d.spaces == s.spaces)));
+    }
+
+    #[test]
+    fn negative_rules_count_dots_added_after_them() {
+        let (spaces, dots) = KROPKI.split_at(16);
+        let line = format!("{};negative:kropki{}", spaces, dots);
+        let grid = line.parse::<Grid4>().unwrap();
+        let last = format!("{};negative:kropki", KROPKI)
+            .parse::<Grid4>()
+            .unwrap();
+
+        assert_eq!(grid, last);
+        assert_eq!(grid.to_line(), last.to_line());
+        assert_eq!(grid.solutions(usize::MAX).len(), 1);
+    }
+
+    #[test]
+    fn dots_are_drawn_between_spaces() {
+        let grid = Grid4::empty()
+            .with_white_dot("r1c1-r1c2")
+            .and_then(|grid| grid.with_x("r1c4-r2c4"))
+            .unwrap();
+        let styled = grid.to_string();
+        let lines = styled.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[1], "│ . ○ . ┃ .   . │");
+        assert_eq!(lines[2], "│       ┃     X │");
+    }
+
+    #[test_case("white:r1c1-r2c2", ParseGridError::InvalidPath("r1c1-r2c2".into()) ; "diagonal")]
+    #[test_case("black:r1c1", ParseGridError::InvalidPath("r1c1".into()) ; "one space")]
+    #[test_case("x:r1c1-r1c2-r1c3", ParseGridError::InvalidPath("r1c1-r1c2-r1c3".into()) ; "three spaces")]
+    #[test_case("negative:dots", ParseGridError::UnknownConstraint("negative:dots".into()) ; "unknown")]
+    fn dots_reject(section: &str, expected: ParseGridError) {
+        let grid = Grid::empty().with_written_constraint(section);
+        assert_eq!(grid.err(), Some(expected));
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    error::Error,
+    fmt,
+    ops::{Not, RangeInclusive},
+};
+
+use rand::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::{space::Space, technique::Technique};
+
+use super::Board;
+
+/// Below this many givens on a 9x9 grid, removing clues one at a time nearly
+/// always gets stuck at a minimal puzzle with too many clues, so we search
+/// instead. Other sizes scale this by their number of spaces.
+const LOW_CLUES: usize = 22;
+
+/// How many times to give back a clue and dig again before giving up on a
+/// solution grid and starting over with a new one.
+const EXCHANGES: usize = 200;
+
+/// How many solution grids to try before giving up on a kind of puzzle that
+/// may never turn up, such as one needing a technique that grids of its size
+/// never call for. The hardest techniques take several hundred on a 9x9 grid.
+pub(super) const MAX_ATTEMPTS: usize = 5_000;
+
+/// A generated puzzle, along with its solution and the number of solution
+/// grids that had to be tried before one could be dug down far enough.
+#[derive(Clone, Debug)]
+pub struct Generated<const BW: usize = 3, const BH: usize = 3, const N: usize = 9> {
+    pub puzzle: Board<BW, BH, N>,
+    pub solution: Board<BW, BH, N>,
+    pub attempts: usize,
+}
+
+/// The error when generating gives up, having found no puzzle of the kind
+/// asked for in `attempts` solution grids.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GaveUp {
+    pub attempts: usize,
+}
+
+impl fmt::Display for GaveUp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gave up after {} attempts without finding a puzzle",
+            self.attempts
+        )
+    }
+}
+
+impl Error for GaveUp {}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// The fewest givens a uniquely solvable sudoku can have: 17 on a 9x9
+    /// grid, 8 on a 6x6 and 4 on a 4x4. Nobody knows for larger grids, which
+    /// get the weaker bound that all but one of the values must be given
+    /// somewhere.
+    pub const MIN_CLUES: usize = match N {
+        4 => 4,
+        6 => 8,
+        9 => 17,
+        _ => N - 1,
+    };
+
+    /// Generate a uniquely solvable puzzle with a number of givens in `clues`,
+    /// giving up after `MAX_ATTEMPTS` solution grids, which can take a very
+    /// long time for the lowest clue counts.
+    pub fn generate<R: Rng + ?Sized>(
+        clues: RangeInclusive<usize>,
+        rng: &mut R,
+    ) -> Result<Generated<BW, BH, N>, GaveUp> {
+        Self::empty().generate_like(clues, rng)
+    }
+
+    /// Generate a puzzle as `generate` does, with the same regions and
+    /// diagonals as this grid, ignoring any of its values.
+    pub fn generate_like<R: Rng + ?Sized>(
+        &self,
+        clues: RangeInclusive<usize>,
+        rng: &mut R,
+    ) -> Result<Generated<BW, BH, N>, GaveUp> {
+        let (min, max) = (*clues.start(), *clues.end());
+        assert!(
+            Self::MIN_CLUES <= min && min <= max && max <= N * N,
+            "clues must be a range within {}..={}",
+            Self::MIN_CLUES,
+            N * N,
+        );
+
+        for attempts in 1..=MAX_ATTEMPTS {
+            let solution = self.random_solution(rng);
+            let target = rng.gen_range(min, max + 1);
+            let puzzle = if target * 81 < LOW_CLUES * N * N {
+                solution.clone().exchange(target, rng)
+            } else {
+                solution.clone().dig(target, rng)
+            };
+
+            if clues.contains(&puzzle.clues()) {
+                return Ok(Generated {
+                    puzzle,
+                    solution,
+                    attempts,
+                });
+            }
+        }
+
+        Err(GaveUp {
+            attempts: MAX_ATTEMPTS,
+        })
+    }
+
+    /// Generate a puzzle that can be solved using `technique` and those
+    /// easier than it, but not without `technique`.
+    pub fn generate_requiring<R: Rng + ?Sized>(
+        technique: Technique,
+        rng: &mut R,
+    ) -> Result<Generated<BW, BH, N>, GaveUp> {
+        Self::generate_graded(technique..=technique, rng)
+    }
+
+    /// Generate a puzzle whose hardest required technique lies in `grades`,
+    /// giving up after `MAX_ATTEMPTS` solution grids, as some grades never
+    /// turn up on small grids.
+    pub fn generate_graded<R: Rng + ?Sized>(
+        grades: RangeInclusive<Technique>,
+        rng: &mut R,
+    ) -> Result<Generated<BW, BH, N>, GaveUp> {
+        Self::empty().generate_graded_like(grades, rng)
+    }
+
+    /// Generate a graded puzzle with the same regions and diagonals as this
+    /// grid, ignoring any of its values.
+    pub fn generate_graded_like<R: Rng + ?Sized>(
+        &self,
+        grades: RangeInclusive<Technique>,
+        rng: &mut R,
+    ) -> Result<Generated<BW, BH, N>, GaveUp> {
+        self.generate_graded_within(grades, MAX_ATTEMPTS, rng)
+    }
+
+    fn generate_graded_within<R: Rng + ?Sized>(
+        &self,
+        grades: RangeInclusive<Technique>,
+        max_attempts: usize,
+        rng: &mut R,
+    ) -> Result<Generated<BW, BH, N>, GaveUp> {
+        let techniques = Technique::iter()
+            .filter(|t| t <= grades.end())
+            .collect::<Vec<Technique>>();
+
+        for attempts in 1..=max_attempts {
+            let solution = self.random_solution(rng);
+            let mut positions = solution.occupied_positions();
+            positions.shuffle(rng);
+
+            // Dig as far as the allowed techniques can still solve. Anything
+            // they can solve without guessing has a unique solution, so this
+            // doubles as the uniqueness check.
+            let mut puzzle = solution.clone();
+            for (x, y) in positions {
+                let value = match puzzle.get(x, y) {
+                    Space::Occupied(v) => v,
+                    Space::Empty => continue,
+                };
+                puzzle.set_empty(x, y);
+                if puzzle.logical_solve_with(&techniques).is_solved().not() {
+                    puzzle.set(x, y, value);
+                }
+            }
+
+            let hardest = puzzle.logical_solve_with(&techniques).hardest();
+            if hardest.filter(|t| grades.contains(t)).is_some() {
+                return Ok(Generated {
+                    puzzle,
+                    solution,
+                    attempts,
+                });
+            }
+        }
+
+        Err(GaveUp {
+            attempts: max_attempts,
+        })
+    }
+
+    /// A random solution grid with the same regions and diagonals as this
+    /// one.
+    pub(super) fn random_solution<R: Rng + ?Sized>(&self, rng: &mut R) -> Self {
+        self.cleared()
+            .randomised_solve(rng)
+            .expect("the regions and diagonals must allow a solution")
+    }
+
+    /// Remove givens in a random order, until `target` is reached or no
+    /// more can be removed without losing uniqueness.
+    pub(super) fn dig<R: Rng + ?Sized>(self, target: usize, rng: &mut R) -> Self {
+        let mut positions = self.occupied_positions();
+        positions.shuffle(rng);
+
+        self.remove_clues(positions, target)
+    }
+
+    fn remove_clues(mut self, positions: Vec<(u8, u8)>, target: usize) -> Self {
+        for (x, y) in positions {
+            if self.clues() <= target {
+                break;
+            }
+            let value = match self.get(x, y) {
+                Space::Occupied(v) => v,
+                Space::Empty => continue,
+            };
+            self.set_empty(x, y);
+            if self.has_unique_solution().not() {
+                self.set(x, y, value);
+            }
+        }
+
+        self
+    }
+
+    /// Dig to a minimal puzzle, then repeatedly give back one of the removed
+    /// clues and dig again without it. A single extra clue often makes two
+    /// or more others redundant, which lets us walk down to clue counts that
+    /// digging alone practically never reaches.
+    fn exchange<R: Rng + ?Sized>(self, target: usize, rng: &mut R) -> Self {
+        let solution = self;
+        let mut puzzle = solution.clone().dig(target, rng);
+
+        for _ in 0..EXCHANGES {
+            if puzzle.clues() <= target {
+                break;
+            }
+
+            let (x, y) = *puzzle.empty_positions().choose(rng).unwrap();
+            let value = match solution.get(x, y) {
+                Space::Occupied(v) => v,
+                Space::Empty => unreachable!("solution grids are complete"),
+            };
+
+            let mut candidate = puzzle.clone();
+            candidate.set(x, y, value);
+            let mut positions = candidate.occupied_positions();
+            positions.retain(|&p| p != (x, y));
+            positions.shuffle(rng);
+            let candidate = candidate.remove_clues(positions, target);
+
+            // Accept sideways moves as well as improvements, so the search
+            // can wander across plateaus of minimal puzzles.
+            if candidate.clues() <= puzzle.clues() {
+                puzzle = candidate;
+            }
+        }
+
+        puzzle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use crate::grid::{Grid, Grid4};
+
+    use super::*;
+
+    #[test_case(30..=35)]
+    #[test_case(28..=28)]
+    fn generate_within_clue_range(clues: RangeInclusive<usize>) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let generated = Grid::generate(clues.clone(), &mut rng).unwrap();
+
+        assert!(clues.contains(&generated.puzzle.clues()));
+        assert!(generated.puzzle.has_unique_solution());
+        assert_eq!(generated.puzzle.solve(), Some(generated.solution));
+        assert!(generated.attempts >= 1);
+    }
+
+    #[test]
+    fn generate_is_reproducible() {
+        let first = Grid::generate(25..=30, &mut StdRng::seed_from_u64(7)).unwrap();
+        let second = Grid::generate(25..=30, &mut StdRng::seed_from_u64(7)).unwrap();
+
+        assert_eq!(first.puzzle, second.puzzle);
+    }
+
+    #[test]
+    fn generate_four_by_four() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let generated = Grid4::generate(Grid4::MIN_CLUES..=6, &mut rng).unwrap();
+
+        assert!(generated.puzzle.clues() <= 6);
+        assert!(generated.puzzle.has_unique_solution());
+        assert!(generated.solution.is_solved());
+    }
+
+    #[test]
+    fn generate_graded_gives_up_on_grades_out_of_reach() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let swordfish = Technique::Swordfish..=Technique::Swordfish;
+        let generated = Grid4::empty().generate_graded_within(swordfish, 20, &mut rng);
+
+        assert_eq!(generated.err(), Some(GaveUp { attempts: 20 }));
+    }
+
+    #[test]
+    fn generate_diagonal() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let generated = Grid::empty()
+            .with_diagonals()
+            .generate_like(24..=28, &mut rng)
+            .unwrap();
+
+        assert!(generated.puzzle.is_diagonal());
+        assert!(generated.puzzle.has_unique_solution());
+        assert!(generated.solution.is_solved());
+        assert!(generated
+            .puzzle
+            .logical_solve()
+            .grid
+            .spaces
+            .iter()
+            .zip(generated.solution.spaces.iter())
+            .all(|(deduced, solved)| deduced.is_occupied().not() || deduced == solved));
+    }
+}
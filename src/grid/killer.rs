@@ -0,0 +1,487 @@
+use std::{ops::Not, sync::Arc};
+
+use rand::prelude::*;
+
+use crate::{candidates::Candidates, space::Space, value::Value};
+
+use super::{
+    constraint::{Constraint, Hint, Propagate},
+    generate::MAX_ATTEMPTS,
+    neighbours, reachable, Board, GaveUp, Generated, ParseGridError,
+};
+
+/// How many spaces the generator makes each cage, picked at random. Small
+/// cages pin the solution down quickly, and the odd larger one keeps things
+/// interesting.
+const CAGE_SIZES: [usize; 10] = [2, 2, 2, 3, 3, 3, 3, 4, 4, 5];
+
+/// A killer cage: spaces whose values must add up to `sum` without any of
+/// them repeating.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(super) struct Cage {
+    /// The indices of the spaces in the cage.
+    pub(super) spaces: Vec<usize>,
+    pub(super) sum: usize,
+    /// Every set of distinct values, one for each space, that adds up to the
+    /// sum.
+    pub(super) combinations: Vec<Candidates>,
+}
+
+impl Cage {
+    fn new(mut spaces: Vec<usize>, sum: usize, size: usize) -> Self {
+        spaces.sort_unstable();
+        let mut combinations = vec![];
+        add_combinations(
+            1,
+            size,
+            spaces.len(),
+            sum,
+            Candidates::none(),
+            &mut combinations,
+        );
+
+        Self {
+            spaces,
+            sum,
+            combinations,
+        }
+    }
+}
+
+impl Constraint for Cage {
+    fn spaces(&self) -> &[usize] {
+        &self.spaces
+    }
+
+    /// A space down to its last candidate takes it from the rest of the cage,
+    /// and the cage keeps only the candidates that a combination adding up to
+    /// its sum could use. A combination is only possible while every space
+    /// can take one of its values, and the spaces between them can take all
+    /// of them.
+    fn prune(&self, grid: &mut dyn Propagate, _removed: Option<(usize, Value)>) -> bool {
+        for &i in self.spaces.iter() {
+            if let Some(last) = grid.candidates(i).single() {
+                let mates = self.spaces.iter().filter(|&&p| p != i);
+                if mates.cloned().all(|p| grid.eliminate(p, last)).not() {
+                    return false;
+                }
+            }
+        }
+
+        let mut allowed = vec![Candidates::none(); self.spaces.len()];
+        for &combination in self.combinations.iter() {
+            let options = self
+                .spaces
+                .iter()
+                .map(|&i| grid.candidates(i).intersection(combination))
+                .collect::<Vec<Candidates>>();
+            let covered = options
+                .iter()
+                .fold(Candidates::none(), |acc, &o| acc.union(o));
+            if options.iter().any(|o| o.is_empty()) || covered != combination {
+                continue;
+            }
+            for (allowed, option) in allowed.iter_mut().zip(options) {
+                *allowed = allowed.union(option);
+            }
+        }
+
+        for (&i, allowed) in self.spaces.iter().zip(allowed) {
+            let ruled_out = grid.candidates(i).difference(allowed);
+            if ruled_out.iter().all(|value| grid.eliminate(i, value)).not() {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Whether some combination adding up to the sum has `value` along with
+    /// every value already in the cage, none of them repeated.
+    fn allows(&self, spaces: &[Space], i: usize, value: Value) -> bool {
+        let mut placed = Some(value).into_iter().collect::<Candidates>();
+        for &p in self.spaces.iter().filter(|&&p| p != i) {
+            if let Space::Occupied(v) = spaces[p] {
+                if placed.contains(v) {
+                    return false;
+                }
+                placed.insert(v);
+            }
+        }
+
+        self.combinations
+            .iter()
+            .any(|&combination| combination.intersection(placed) == placed)
+    }
+
+    /// Whether the values are all filled, add up to the sum and don't repeat.
+    fn holds(&self, spaces: &[Space]) -> bool {
+        let mut values = Candidates::none();
+        let mut total = 0;
+        for &i in self.spaces.iter() {
+            match spaces[i] {
+                Space::Occupied(value) if values.contains(value).not() => {
+                    values.insert(value);
+                    total += usize::from(u8::from(value));
+                }
+                _ => return false,
+            }
+        }
+
+        total == self.sum
+    }
+
+    fn hints(&self) -> Vec<Hint> {
+        vec![Hint::Outline(self.spaces.clone(), self.sum.to_string())]
+    }
+}
+
+/// Add every set of `count` distinct values from `from` up to `size` that adds
+/// up to `sum` to `combinations`, each along with the values in `chosen`.
+pub(super) fn add_combinations(
+    from: usize,
+    size: usize,
+    count: usize,
+    sum: usize,
+    chosen: Candidates,
+    combinations: &mut Vec<Candidates>,
+) {
+    if count == 0 {
+        if sum == 0 {
+            combinations.push(chosen);
+        }
+        return;
+    }
+
+    for value in from..=size {
+        // The smallest values left are already too big.
+        if value * count + count * (count - 1) / 2 > sum {
+            break;
+        }
+        let mut chosen = chosen;
+        chosen.insert(Value::from(value as u8));
+        add_combinations(
+            value + 1,
+            size,
+            count - 1,
+            sum - value,
+            chosen,
+            combinations,
+        );
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Add killer cages from a layer with a symbol for each space in
+    /// row-major order, followed by `=` and the sum of each cage. Spaces next
+    /// to each other with the same symbol are in the same cage, and `.` is in
+    /// none. The sums are separated by commas or whitespace, and given in
+    /// the order the cages are first met in the layer.
+    pub fn with_cages(mut self, cages: &str) -> Result<Self, ParseGridError> {
+        let (layer, sums) = cages.split_once('=').unwrap_or((cages, ""));
+        let symbols = layer
+            .chars()
+            .filter(|&c| c == '.' || c.is_ascii_alphanumeric())
+            .collect::<Vec<char>>();
+        if symbols.len() != N * N {
+            return Err(ParseGridError::CageSpaces(symbols.len()));
+        }
+        let sums = sums
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|sum| sum.is_empty().not())
+            .map(|sum| {
+                sum.parse::<usize>()
+                    .map_err(|_| ParseGridError::InvalidSum(sum.to_string()))
+            })
+            .collect::<Result<Vec<usize>, ParseGridError>>()?;
+
+        let mut spaces = vec![];
+        let mut caged = vec![false; N * N];
+        for i in 0..N * N {
+            if symbols[i] == '.' || caged[i] {
+                continue;
+            }
+            let cage = reachable(i, N, |n| symbols[n] == symbols[i]);
+            for &n in cage.iter() {
+                caged[n] = true;
+            }
+            spaces.push(cage);
+        }
+        if spaces.len() != sums.len() {
+            return Err(ParseGridError::CageSums {
+                cages: spaces.len(),
+                sums: sums.len(),
+            });
+        }
+
+        let mut cages = vec![];
+        for (n, (spaces, sum)) in spaces.into_iter().zip(sums).enumerate() {
+            let cage = Cage::new(spaces, sum, N);
+            if cage.spaces.len() > N || cage.combinations.is_empty() {
+                return Err(ParseGridError::InvalidCage(n + 1));
+            }
+            cages.push(cage);
+        }
+        self = self.with_cage_list(cages);
+
+        Ok(self)
+    }
+
+    /// The same grid with `cages` in place of any it had, in the order
+    /// they're first met in row-major order.
+    fn with_cage_list(mut self, mut cages: Vec<Cage>) -> Self {
+        cages.sort_by_key(|cage| cage.spaces[0]);
+        self.rules = Arc::new(self.rules.with_cages(cages, N * N));
+
+        self
+    }
+
+    /// Whether the grid has killer cages.
+    pub fn is_killer(&self) -> bool {
+        self.rules.cages.is_empty().not()
+    }
+
+    /// How many killer cages the grid has.
+    pub fn cage_count(&self) -> usize {
+        self.rules.cages.len()
+    }
+
+    /// The cage layer and sums as they're written by `to_line`, with
+    /// neighbouring cages given different letters.
+    pub(super) fn cages_line(&self) -> String {
+        let letters = ('a'..='z').chain('A'..='Z').collect::<Vec<char>>();
+        let mut symbols = vec!['.'; N * N];
+        for cage in self.rules.cages.iter() {
+            let taken = cage
+                .spaces
+                .iter()
+                .flat_map(|&i| neighbours(i, N))
+                .map(|n| symbols[n])
+                .collect::<Vec<char>>();
+            let letter = letters
+                .iter()
+                .cloned()
+                .find(|l| taken.contains(l).not())
+                .expect("a cage has fewer neighbouring cages than there are letters");
+            for &i in cage.spaces.iter() {
+                symbols[i] = letter;
+            }
+        }
+
+        let mut line = symbols.into_iter().collect::<String>();
+        line.push('=');
+        let sums = self
+            .rules
+            .cages
+            .iter()
+            .map(|cage| cage.sum.to_string())
+            .collect::<Vec<String>>();
+        line.push_str(&sums.join(","));
+
+        line
+    }
+
+    /// Generate a killer puzzle with no givens, where the cages alone pin
+    /// down the solution, giving up after `MAX_ATTEMPTS` solution grids.
+    pub fn generate_killer<R: Rng + ?Sized>(rng: &mut R) -> Result<Generated<BW, BH, N>, GaveUp> {
+        Self::empty().generate_killer_like(rng)
+    }
+
+    /// Generate a killer puzzle with the same regions and diagonals as this
+    /// grid, ignoring any of its values.
+    pub fn generate_killer_like<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<Generated<BW, BH, N>, GaveUp> {
+        for attempts in 1..=MAX_ATTEMPTS {
+            let solution = self.random_solution(rng);
+            let cages = solution.random_cages(rng);
+            if cages.iter().any(|cage| cage.len() == 1) {
+                continue;
+            }
+            let cages = cages
+                .into_iter()
+                .map(|spaces| {
+                    let sum = spaces
+                        .iter()
+                        .filter_map(|&i| match solution.spaces[i] {
+                            Space::Occupied(v) => Some(usize::from(u8::from(v))),
+                            Space::Empty => None,
+                        })
+                        .sum();
+                    Cage::new(spaces, sum, N)
+                })
+                .collect();
+            let solution = solution.with_cage_list(cages);
+
+            let puzzle = solution.cleared();
+            if puzzle.has_unique_solution() {
+                return Ok(Generated {
+                    puzzle,
+                    solution,
+                    attempts,
+                });
+            }
+        }
+
+        Err(GaveUp {
+            attempts: MAX_ATTEMPTS,
+        })
+    }
+
+    /// Divide a solution grid into cages of joined up spaces, none of which
+    /// repeat a value.
+    fn random_cages<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<Vec<usize>> {
+        let mut order = (0..N * N).collect::<Vec<usize>>();
+        order.shuffle(rng);
+
+        let mut caged = vec![false; N * N];
+        let mut cages = vec![];
+        for start in order {
+            if caged[start] {
+                continue;
+            }
+            let size = *CAGE_SIZES.choose(rng).unwrap();
+            let mut cage = vec![start];
+            caged[start] = true;
+            while cage.len() < size {
+                let options = cage
+                    .iter()
+                    .flat_map(|&i| neighbours(i, N))
+                    .filter(|&n| caged[n].not())
+                    .filter(|&n| cage.iter().all(|&i| self.spaces[i] != self.spaces[n]))
+                    .collect::<Vec<usize>>();
+                match options.choose(rng) {
+                    Some(&n) => {
+                        cage.push(n);
+                        caged[n] = true;
+                    }
+                    None => break,
+                }
+            }
+            cages.push(cage);
+        }
+
+        // A cage of one space would be a given in disguise, so join each to
+        // a neighbouring cage that doesn't already hold its value.
+        let mut cage_of = vec![0; N * N];
+        for (c, cage) in cages.iter().enumerate() {
+            for &i in cage.iter() {
+                cage_of[i] = c;
+            }
+        }
+        for c in 0..cages.len() {
+            if cages[c].len() != 1 {
+                continue;
+            }
+            let i = cages[c][0];
+            let options = neighbours(i, N)
+                .map(|n| cage_of[n])
+                .filter(|&d| d != c)
+                .filter(|&d| cages[d].iter().all(|&p| self.spaces[p] != self.spaces[i]))
+                .collect::<Vec<usize>>();
+            if let Some(&d) = options.choose(rng) {
+                cages[c].clear();
+                cages[d].push(i);
+                cage_of[i] = d;
+            }
+        }
+        cages.retain(|cage| cage.is_empty().not());
+
+        cages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fehler::throws;
+    use test_case::test_case;
+
+    use crate::grid::{Grid, Grid4};
+
+    use super::*;
+
+    const KILLER: &str = concat!(
+        "aabaabaabbbbbabacbaccddbbcbabbacaacccbbccabbcccaadbbaacbbcddbcbcadccaccbaadbbaabb",
+        "=9,23,16,19,21,9,32,10,7,9,23,7,17,6,26,25,10,18,12,10,15,10,21,11,15,17,7",
+    );
+
+    #[test_case(2, 3, &[0b11])]
+    #[test_case(2, 5, &[0b1001, 0b110])]
+    #[test_case(3, 6, &[0b111])]
+    #[test_case(2, 18, &[])]
+    fn combinations_add_up(count: usize, sum: usize, expected: &[u32]) {
+        let mut combinations = vec![];
+        add_combinations(1, 9, count, sum, Candidates::none(), &mut combinations);
+
+        let bits = combinations
+            .iter()
+            .map(|c| c.iter().map(|v| 1 << (u8::from(v) - 1)).sum())
+            .collect::<Vec<u32>>();
+        assert_eq!(bits, expected);
+    }
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn killer_solves_from_cages_alone() {
+        let grid: Grid = KILLER.parse()?;
+        assert!(grid.is_killer());
+        assert_eq!(grid.clues(), 0);
+        assert_eq!(grid.cage_count(), 27);
+        assert_eq!(grid.to_line(), format!("{}{}", ".".repeat(81), KILLER));
+
+        let solution = grid.clone().solve().unwrap();
+        assert!(solution.is_solved());
+        assert_eq!(grid.count_solutions(2), 1);
+        assert!(Grid::new_solved().with_cages(KILLER)?.is_solved().not());
+
+        // Backtracking needs a head start to finish in good time.
+        let mut started = grid;
+        for i in 0..27 {
+            started.spaces[i] = solution.spaces[i];
+        }
+        assert_eq!(started.backtracking_solve(), Some(solution));
+    }
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn killer_renders_cage_sums() {
+        let grid: Grid = KILLER.parse()?;
+        let styled = grid.to_string();
+        let mut lines = styled.lines();
+
+        assert_eq!(lines.next(), Some("┌9  ─────23 ─16 ─────19 ─21 ─────9  ┐"));
+        assert_eq!(lines.next(), Some("│ .   . ╎ . ┃ .   . ╎ . ┃ .   . ╎ . │"));
+        assert_eq!(lines.next(), Some("│╌╌╌╌╌╌╌┘   ┃╌╌╌┐   ╎   ┃   ┌32 ┤   │"));
+    }
+
+    #[test_case("aa", ParseGridError::CageSpaces(2) ; "too few spaces")]
+    #[test_case(&format!("{}=1,x", "a".repeat(81)), ParseGridError::InvalidSum("x".into()) ; "bad sum")]
+    #[test_case(&format!("{}=1,2", "a".repeat(81)), ParseGridError::CageSums { cages: 1, sums: 2 } ; "extra sum")]
+    #[test_case(&format!("{}=45", "a".repeat(81)), ParseGridError::InvalidCage(1) ; "too big")]
+    #[test_case(&format!("ab{}=4,45,1", "c".repeat(79)), ParseGridError::InvalidCage(2) ; "impossible sum")]
+    fn killer_rejects(cages: &str, expected: ParseGridError) {
+        assert_eq!(Grid::empty().with_cages(cages).err(), Some(expected));
+    }
+
+    #[test]
+    fn generate_killer_four_by_four() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let generated = Grid4::generate_killer(&mut rng).unwrap();
+
+        assert_eq!(generated.puzzle.clues(), 0);
+        assert!(generated.puzzle.is_killer());
+        assert_eq!(generated.puzzle.solve(), Some(generated.solution));
+    }
+
+    #[test]
+    fn random_cages_leave_no_space_alone() {
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let cages = Grid::new_solved().random_cages(&mut rng);
+
+            assert!(cages.iter().all(|cage| cage.len() > 1));
+            assert_eq!(cages.iter().map(Vec::len).sum::<usize>(), 81);
+        }
+    }
+}
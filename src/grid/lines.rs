@@ -0,0 +1,362 @@
+use std::ops::Not;
+
+use crate::{candidates::Candidates, space::Space, value::Value};
+
+use super::{
+    constraint::{Constraint, Hint, Propagate},
+    Board, ParseGridError,
+};
+
+pub(super) fn number(value: Value) -> usize {
+    usize::from(u8::from(value))
+}
+
+/// The lowest and highest of some candidates, which mustn't be empty.
+pub(super) fn bounds(candidates: Candidates) -> (usize, usize) {
+    let mut values = candidates.iter().map(number);
+    let lowest = values.next().unwrap_or_default();
+
+    (lowest, values.last().unwrap_or(lowest))
+}
+
+/// Rule out every candidate of space `i` outside `low..=high`.
+pub(super) fn keep_between(grid: &mut dyn Propagate, i: usize, low: usize, high: usize) -> bool {
+    let outside = grid
+        .candidates(i)
+        .iter()
+        .filter(|&v| number(v) < low || number(v) > high)
+        .collect::<Vec<Value>>();
+
+    outside.into_iter().all(|v| grid.eliminate(i, v))
+}
+
+/// The space a cell like `r1c2` names, by its row and then its column,
+/// counting from 1.
+pub(super) fn parse_cell(cell: &str, size: usize) -> Option<usize> {
+    let cell = cell.to_ascii_lowercase();
+    let (row, column) = cell.strip_prefix('r')?.split_once('c')?;
+    let (row, column) = (row.parse::<usize>().ok()?, column.parse::<usize>().ok()?);
+    let inside = |n| (1..=size).contains(&n);
+
+    (inside(row) && inside(column)).then(|| (row - 1) * size + column - 1)
+}
+
+/// Read a path of spaces written like `r1c1-r1c2-r2c3`, where each space
+/// touches the one before across, down or diagonally, and none is visited
+/// twice.
+pub(super) fn parse_path(path: &str, size: usize) -> Result<Vec<usize>, ParseGridError> {
+    let spaces = path
+        .split(|c: char| c == '-' || c == ',' || c.is_whitespace())
+        .filter(|cell| cell.is_empty().not())
+        .map(|cell| parse_cell(cell, size).ok_or_else(|| ParseGridError::InvalidCell(cell.into())))
+        .collect::<Result<Vec<usize>, ParseGridError>>()?;
+
+    let touching = |a: usize, b: usize| {
+        (a % size).abs_diff(b % size) <= 1 && (a / size).abs_diff(b / size) <= 1
+    };
+    let revisits = (1..spaces.len()).any(|n| spaces[..n].contains(&spaces[n]));
+    if revisits || spaces.windows(2).any(|w| touching(w[0], w[1]).not()) {
+        return Err(ParseGridError::InvalidPath(path.trim().into()));
+    }
+
+    Ok(spaces)
+}
+
+/// A path written as it's read by `parse_path`.
+pub(super) fn path_line(spaces: &[usize], size: usize) -> String {
+    let cells = spaces
+        .iter()
+        .map(|&i| format!("r{}c{}", i / size + 1, i % size + 1))
+        .collect::<Vec<String>>();
+
+    cells.join("-")
+}
+
+/// A thermometer: spaces from the bulb to the tip, whose values must rise
+/// all the way along.
+struct Thermo {
+    spaces: Vec<usize>,
+    size: usize,
+}
+
+impl Constraint for Thermo {
+    fn spaces(&self) -> &[usize] {
+        &self.spaces
+    }
+
+    /// Each space must be higher than the lowest the space before it can
+    /// be, and lower than the highest the space after it can be.
+    fn prune(&self, grid: &mut dyn Propagate, _removed: Option<(usize, Value)>) -> bool {
+        let mut lows = vec![];
+        let mut low = 0;
+        for &i in self.spaces.iter() {
+            match grid.candidates(i).iter().map(number).find(|&v| v > low) {
+                Some(v) => low = v,
+                None => return false,
+            }
+            lows.push(low);
+        }
+
+        let mut high = self.size + 1;
+        for (&i, &low) in self.spaces.iter().zip(lows.iter()).rev() {
+            match grid
+                .candidates(i)
+                .iter()
+                .map(number)
+                .filter(|&v| v < high)
+                .last()
+            {
+                Some(v) => high = v,
+                None => return false,
+            }
+            if keep_between(grid, i, low, high).not() {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Whether `value` leaves room for the spaces either side of it to rise
+    /// from the bulb, given the values already along the thermometer.
+    fn allows(&self, spaces: &[Space], i: usize, value: Value) -> bool {
+        let k = self.spaces.iter().position(|&p| p == i).unwrap();
+        let v = number(value);
+        if v <= k || v + (self.spaces.len() - 1 - k) > self.size {
+            return false;
+        }
+
+        self.spaces
+            .iter()
+            .enumerate()
+            .all(|(j, &p)| match spaces[p] {
+                Space::Occupied(w) if p != i && j < k => number(w) + (k - j) <= v,
+                Space::Occupied(w) if p != i => v + (j - k) <= number(w),
+                _ => true,
+            })
+    }
+
+    fn holds(&self, spaces: &[Space]) -> bool {
+        let mut last = 0;
+        for &i in self.spaces.iter() {
+            match spaces[i] {
+                Space::Occupied(v) if number(v) > last => last = number(v),
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    fn hints(&self) -> Vec<Hint> {
+        vec![
+            Hint::Circle(self.spaces[0]),
+            Hint::Line(self.spaces.clone()),
+        ]
+    }
+
+    fn to_line(&self) -> Option<String> {
+        Some(format!("thermo:{}", path_line(&self.spaces, self.size)))
+    }
+}
+
+/// An arrow from a circled space, whose value must be the sum of the values
+/// along the arrow. The circle comes first in `spaces`.
+struct Arrow {
+    spaces: Vec<usize>,
+    size: usize,
+}
+
+impl Constraint for Arrow {
+    fn spaces(&self) -> &[usize] {
+        &self.spaces
+    }
+
+    /// The circle must be somewhere between the least and the most the
+    /// arrow can add up to, and each space along the arrow must make up what
+    /// the circle needs once the rest are as big, or as small, as they can
+    /// be.
+    fn prune(&self, grid: &mut dyn Propagate, _removed: Option<(usize, Value)>) -> bool {
+        let (circle, path) = (self.spaces[0], &self.spaces[1..]);
+        let sums = |grid: &dyn Propagate| {
+            path.iter()
+                .map(|&i| bounds(grid.candidates(i)))
+                .fold((0, 0), |(least, most), (low, high)| {
+                    (least + low, most + high)
+                })
+        };
+
+        let (least, most) = sums(grid);
+        if keep_between(grid, circle, least, most).not() {
+            return false;
+        }
+
+        for &i in path.iter() {
+            let (least, most) = sums(grid);
+            let (low, high) = bounds(grid.candidates(circle));
+            let (here_low, here_high) = bounds(grid.candidates(i));
+            let (rest_least, rest_most) = (least - here_low, most - here_high);
+            let high = match high.checked_sub(rest_least) {
+                Some(high) => high,
+                None => return false,
+            };
+            if keep_between(grid, i, low.saturating_sub(rest_most), high).not() {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Whether the arrow could still add up to the circle with `value` in
+    /// space `i`.
+    fn allows(&self, spaces: &[Space], i: usize, value: Value) -> bool {
+        let value_at = |p: usize| match spaces[p] {
+            _ if p == i => Some(number(value)),
+            Space::Occupied(v) => Some(number(v)),
+            Space::Empty => None,
+        };
+        let path = &self.spaces[1..];
+        let placed = path.iter().filter_map(|&p| value_at(p)).sum::<usize>();
+        let empty = path.iter().filter(|&&p| value_at(p).is_none()).count();
+        let (least, most) = (placed + empty, placed + empty * self.size);
+
+        match value_at(self.spaces[0]) {
+            Some(circle) => least <= circle && circle <= most,
+            None => least <= self.size,
+        }
+    }
+
+    fn holds(&self, spaces: &[Space]) -> bool {
+        let mut values = self.spaces.iter().map(|&i| match spaces[i] {
+            Space::Occupied(v) => Some(number(v)),
+            Space::Empty => None,
+        });
+        let circle = values.next().flatten();
+        let sum = values.sum::<Option<usize>>();
+
+        circle.is_some() && circle == sum
+    }
+
+    fn hints(&self) -> Vec<Hint> {
+        vec![
+            Hint::Circle(self.spaces[0]),
+            Hint::Arrow(self.spaces.clone()),
+        ]
+    }
+
+    fn to_line(&self) -> Option<String> {
+        Some(format!("arrow:{}", path_line(&self.spaces, self.size)))
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Add a thermometer along `path`, written like `r1c1-r1c2-r2c3` from the
+    /// bulb to the tip, where the values must rise from the bulb. Each space
+    /// must touch the one before, across, down or diagonally.
+    pub fn with_thermo(self, path: &str) -> Result<Self, ParseGridError> {
+        let spaces = parse_path(path, N)?;
+        if spaces.len() < 2 || spaces.len() > N {
+            return Err(ParseGridError::InvalidPath(path.trim().into()));
+        }
+
+        Ok(self.with_constraint(Thermo { spaces, size: N }))
+    }
+
+    /// Add an arrow along `path`, written as for `with_thermo` from the
+    /// circled space to the tip, where the circled value is the sum of the
+    /// rest. Values may repeat along the arrow, unless a unit rules it out.
+    pub fn with_arrow(self, path: &str) -> Result<Self, ParseGridError> {
+        let spaces = parse_path(path, N)?;
+        if spaces.len() < 2 {
+            return Err(ParseGridError::InvalidPath(path.trim().into()));
+        }
+
+        Ok(self.with_constraint(Arrow { spaces, size: N }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ops::Not, str::FromStr};
+
+    use fehler::throws;
+    use test_case::test_case;
+
+    use crate::grid::{Grid, Grid4};
+
+    use super::*;
+
+    /// A grid with a thermometer and an arrow, which only two solutions
+    /// follow.
+    const LINES: &str = concat!(
+        "................",
+        ";thermo:r1c2-r2c3-r3c4",
+        ";arrow:r4c1-r3c1-r2c1",
+    );
+
+    #[test_case("r1c1", Some(0))]
+    #[test_case("R2C3", Some(6))]
+    #[test_case("r4c4", Some(15))]
+    #[test_case("r5c1", None)]
+    #[test_case("r0c1", None)]
+    #[test_case("c1r1", None)]
+    fn cells_are_named_by_row_then_column(cell: &str, expected: Option<usize>) {
+        assert_eq!(parse_cell(cell, 4), expected);
+    }
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn lines_round_trip_and_solve() {
+        let grid: Grid4 = LINES.parse()?;
+        assert_eq!(grid.to_line(), LINES);
+
+        let solutions = grid.solutions(usize::MAX);
+        assert_eq!(solutions.len(), 2);
+        for solution in solutions.iter() {
+            let value = |i: usize| match solution.spaces[i] {
+                Space::Occupied(v) => number(v),
+                Space::Empty => 0,
+            };
+            assert!(solution.is_solved());
+            assert!(value(1) < value(6) && value(6) < value(11));
+            assert_eq!(value(12), value(8) + value(4));
+        }
+
+        let solution = grid.clone().backtracking_solve().unwrap();
+        assert!(solutions.contains(&solution));
+
+        // Some grid the givens and units allow breaks the lines.
+        let mut broken = grid.clone();
+        broken.spaces = Grid4::from_str(&LINES[..16])?
+            .solutions(usize::MAX)
+            .into_iter()
+            .map(|s| s.spaces)
+            .find(|&spaces| solutions.iter().all(|s| s.spaces != spaces))
+            .unwrap();
+        assert!(broken.is_solved().not());
+    }
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn lines_are_drawn_through_the_gaps() {
+        let grid: Grid4 = LINES.parse()?;
+        let styled = grid.to_string();
+        let lines = styled.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[1], "│ .  (.)┃ .   . │");
+        assert_eq!(lines[2], "│       ╲       │");
+        assert_eq!(lines[4], "│ ↑ ━━━━╋━━━╲━━━│");
+        assert_eq!(lines[7], "│(.)  . ┃ .   . │");
+    }
+
+    #[test_case("thermo:r1c1-r1c3", ParseGridError::InvalidPath("r1c1-r1c3".into()) ; "gap")]
+    #[test_case("thermo:r1c1-r1c2-r1c1", ParseGridError::InvalidPath("r1c1-r1c2-r1c1".into()) ; "revisit")]
+    #[test_case("thermo:r1c1", ParseGridError::InvalidPath("r1c1".into()) ; "too short")]
+    #[test_case("arrow:r1c1-r1c10", ParseGridError::InvalidCell("r1c10".into()) ; "outside")]
+    #[test_case("whisper:r1c1-r1c2", ParseGridError::UnknownConstraint("whisper".into()) ; "unknown")]
+    fn lines_reject(section: &str, expected: ParseGridError) {
+        let grid = Grid::empty().with_written_constraint(section);
+        assert_eq!(grid.err(), Some(expected));
+    }
+}
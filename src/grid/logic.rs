@@ -0,0 +1,435 @@
+use std::{ops::Not, sync::Arc};
+
+use strum::IntoEnumIterator;
+
+use crate::{candidates::Candidates, space::Space, technique::Technique, value::Value};
+
+use super::{Board, Cells, Constraint, Layout};
+
+/// The outcome of solving a grid by technique alone, without guessing.
+#[derive(Clone, Debug)]
+pub struct LogicalSolve<const BW: usize = 3, const BH: usize = 3, const N: usize = 9> {
+    /// The grid with every value that could be deduced filled in.
+    pub grid: Board<BW, BH, N>,
+    /// Each technique that made progress, in the order it was applied.
+    pub steps: Vec<Technique>,
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> LogicalSolve<BW, BH, N> {
+    pub fn is_solved(&self) -> bool {
+        self.grid.is_solved()
+    }
+
+    pub fn hardest(&self) -> Option<Technique> {
+        self.steps.iter().max().cloned()
+    }
+}
+
+/// A grid along with the candidates still open to each of its empty spaces.
+#[derive(Clone)]
+struct Marks<const BW: usize, const BH: usize, const N: usize> {
+    grid: Board<BW, BH, N>,
+    candidates: Cells<Candidates, N>,
+    layout: Arc<Layout>,
+}
+
+fn combinations<T: Copy>(items: &[T], size: usize) -> Vec<Vec<T>> {
+    if size == 0 {
+        return vec![vec![]];
+    }
+
+    let mut combos = vec![];
+    for (n, &item) in items.iter().enumerate() {
+        for mut rest in combinations(&items[n + 1..], size - 1) {
+            rest.insert(0, item);
+            combos.push(rest);
+        }
+    }
+
+    combos
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Marks<BW, BH, N> {
+    fn new(grid: Board<BW, BH, N>) -> Self {
+        let layout = Arc::clone(&grid.layout);
+        let mut candidates = Cells::filled(Candidates::none());
+        for (i, space) in grid.spaces.iter().enumerate() {
+            if space.is_occupied() {
+                continue;
+            }
+            candidates[i] = Candidates::all(N);
+            for &p in layout.peers[i].iter() {
+                if let Space::Occupied(v) = grid.spaces[p] {
+                    candidates[i].remove(v);
+                }
+            }
+        }
+
+        let mut marks = Self {
+            grid,
+            candidates,
+            layout,
+        };
+        for i in 0..N * N {
+            marks.restrict(i);
+        }
+
+        marks
+    }
+
+    fn place(&mut self, i: usize, value: Value) {
+        self.grid.spaces[i] = Space::Occupied(value);
+        self.candidates[i] = Candidates::none();
+        for &p in self.layout.peers[i].iter() {
+            self.candidates[p].remove(value);
+        }
+
+        let rules = self.grid.rules.clone();
+        for &r in rules.of(i).iter() {
+            for &p in rules.get(r).spaces() {
+                self.restrict(p);
+            }
+        }
+    }
+
+    /// Remove the candidates of space `i` that any of the grid's rules
+    /// beyond its units, such as cages or an anti-knight rule, don't allow.
+    fn restrict(&mut self, i: usize) {
+        let (rules, spaces) = (&self.grid.rules, self.grid.spaces.as_slice());
+        for value in self.candidates[i].iter() {
+            if rules
+                .of(i)
+                .iter()
+                .any(|&r| rules.get(r).allows(spaces, i, value).not())
+            {
+                self.candidates[i].remove(value);
+            }
+        }
+    }
+
+    fn positions(&self, unit: &[usize], value: Value) -> Vec<usize> {
+        unit.iter()
+            .cloned()
+            .filter(|&i| self.candidates[i].contains(value))
+            .collect()
+    }
+
+    /// Apply `technique` wherever it makes progress, returning whether it did.
+    fn apply(&mut self, technique: Technique) -> bool {
+        match technique {
+            Technique::NakedSingle => self.naked_single(),
+            Technique::HiddenSingle => self.hidden_single(),
+            Technique::LockedCandidates => self.locked_candidates(),
+            Technique::NakedPair => self.naked_subset(2),
+            Technique::HiddenPair => self.hidden_subset(2),
+            Technique::NakedTriple => self.naked_subset(3),
+            Technique::HiddenTriple => self.hidden_subset(3),
+            Technique::NakedQuad => self.naked_subset(4),
+            Technique::HiddenQuad => self.hidden_subset(4),
+            Technique::XWing => self.fish(2),
+            Technique::YWing => self.y_wing(),
+            Technique::Swordfish => self.fish(3),
+        }
+    }
+
+    fn naked_single(&mut self) -> bool {
+        for i in 0..N * N {
+            if let Some(value) = self.candidates[i].single() {
+                self.place(i, value);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn hidden_single(&mut self) -> bool {
+        for unit in self.layout.units.iter().map(Constraint::spaces) {
+            for value in Value::all(N) {
+                let positions = self.positions(unit, value);
+                if positions.len() == 1 {
+                    self.place(positions[0], value);
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// When a value's candidates in one unit all fall inside another unit,
+    /// the value must go there, so it can be removed from the rest of it.
+    fn locked_candidates(&mut self) -> bool {
+        let mut progress = false;
+        let units = &self.layout.units;
+        for (u, unit) in units.iter().map(Constraint::spaces).enumerate() {
+            for value in Value::all(N) {
+                let positions = self.positions(unit, value);
+                if positions.len() < 2 {
+                    continue;
+                }
+                for (o, other) in units.iter().map(Constraint::spaces).enumerate() {
+                    if o == u || positions.iter().all(|p| other.contains(p)).not() {
+                        continue;
+                    }
+                    for &i in other.iter().filter(|i| unit.contains(i).not()) {
+                        progress |= self.candidates[i].remove(value);
+                    }
+                }
+            }
+        }
+
+        progress
+    }
+
+    /// When `size` spaces in a unit share only `size` candidates between
+    /// them, those values can be removed from the rest of the unit.
+    fn naked_subset(&mut self, size: usize) -> bool {
+        let mut progress = false;
+        for unit in self.layout.units.iter().map(Constraint::spaces) {
+            let spaces = unit
+                .iter()
+                .cloned()
+                .filter(|&i| (2..=size).contains(&self.candidates[i].len()))
+                .collect::<Vec<usize>>();
+            for subset in combinations(&spaces, size) {
+                let values = subset
+                    .iter()
+                    .fold(Candidates::none(), |acc, &i| acc.union(self.candidates[i]));
+                if values.len() != size {
+                    continue;
+                }
+                for &i in unit.iter().filter(|i| subset.contains(i).not()) {
+                    for value in values.iter() {
+                        progress |= self.candidates[i].remove(value);
+                    }
+                }
+            }
+        }
+
+        progress
+    }
+
+    /// When `size` values can only go in the same `size` spaces of a unit,
+    /// every other candidate can be removed from those spaces.
+    fn hidden_subset(&mut self, size: usize) -> bool {
+        let mut progress = false;
+        for unit in self.layout.units.iter().map(Constraint::spaces) {
+            let values = Value::all(N)
+                .filter(|&v| (2..=size).contains(&self.positions(unit, v).len()))
+                .collect::<Vec<Value>>();
+            for subset in combinations(&values, size) {
+                let mut spaces = subset
+                    .iter()
+                    .flat_map(|&v| self.positions(unit, v))
+                    .collect::<Vec<usize>>();
+                spaces.sort();
+                spaces.dedup();
+                if spaces.len() != size {
+                    continue;
+                }
+                let keep = subset.iter().cloned().collect::<Candidates>();
+                for i in spaces {
+                    let before = self.candidates[i];
+                    self.candidates[i] = before.intersection(keep);
+                    progress |= self.candidates[i] != before;
+                }
+            }
+        }
+
+        progress
+    }
+
+    /// When a value's candidates in `size` rows all fall in the same `size`
+    /// columns (or vice versa), it can be removed from the rest of those
+    /// columns. An X-Wing has two lines, a Swordfish three.
+    fn fish(&mut self, size: usize) -> bool {
+        let mut progress = false;
+        // The first N units are the rows, and the next N the columns.
+        let units = &self.layout.units;
+        for (base, cover) in [(0..N, N..2 * N), (N..2 * N, 0..N)].iter() {
+            for value in Value::all(N) {
+                let lines = base
+                    .clone()
+                    .filter(|&u| {
+                        (2..=size).contains(&self.positions(units[u].spaces(), value).len())
+                    })
+                    .collect::<Vec<usize>>();
+                for subset in combinations(&lines, size) {
+                    let spaces = subset
+                        .iter()
+                        .flat_map(|&u| self.positions(units[u].spaces(), value))
+                        .collect::<Vec<usize>>();
+                    let covers = cover
+                        .clone()
+                        .filter(|&c| spaces.iter().any(|i| units[c].spaces().contains(i)))
+                        .collect::<Vec<usize>>();
+                    if covers.len() != size {
+                        continue;
+                    }
+                    for c in covers {
+                        for &i in units[c]
+                            .spaces()
+                            .iter()
+                            .filter(|i| spaces.contains(i).not())
+                        {
+                            progress |= self.candidates[i].remove(value);
+                        }
+                    }
+                }
+            }
+        }
+
+        progress
+    }
+
+    /// A pivot with candidates {a, b} sees two pincers with {a, c} and
+    /// {b, c}. Whichever the pivot turns out to be, one pincer is c, so c
+    /// can be removed from every space that sees both pincers.
+    fn y_wing(&mut self) -> bool {
+        let peers = &self.layout.peers;
+        for pivot in 0..N * N {
+            let pivot_candidates = self.candidates[pivot];
+            if pivot_candidates.len() != 2 {
+                continue;
+            }
+            let pincers = peers[pivot]
+                .iter()
+                .cloned()
+                .filter(|&p| {
+                    let c = self.candidates[p];
+                    c.len() == 2 && c.intersection(pivot_candidates).len() == 1
+                })
+                .collect::<Vec<usize>>();
+            for (n, &first) in pincers.iter().enumerate() {
+                for &second in pincers[n + 1..].iter() {
+                    let (a, b) = (self.candidates[first], self.candidates[second]);
+                    let shared = a.intersection(b);
+                    let value = match shared.single() {
+                        Some(value) => value,
+                        None => continue,
+                    };
+                    if pivot_candidates.contains(value)
+                        || a.union(b).difference(shared) != pivot_candidates
+                    {
+                        continue;
+                    }
+
+                    let mut progress = false;
+                    for &i in peers[first].iter() {
+                        if i != second && peers[second].contains(&i) {
+                            progress |= self.candidates[i].remove(value);
+                        }
+                    }
+                    if progress {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        false
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Solve without guessing, always using the easiest technique that makes
+    /// progress, until the grid is solved or no technique can go further.
+    pub fn logical_solve(&self) -> LogicalSolve<BW, BH, N> {
+        self.logical_solve_with(&Technique::iter().collect::<Vec<Technique>>())
+    }
+
+    pub(super) fn logical_solve_with(&self, techniques: &[Technique]) -> LogicalSolve<BW, BH, N> {
+        let mut techniques = techniques.to_vec();
+        techniques.sort();
+
+        let mut marks = Marks::new(self.clone());
+        let mut steps = vec![];
+        'progress: loop {
+            for &technique in techniques.iter() {
+                if marks.apply(technique) {
+                    steps.push(technique);
+                    continue 'progress;
+                }
+            }
+
+            break;
+        }
+
+        LogicalSolve {
+            grid: marks.grid,
+            steps,
+        }
+    }
+
+    /// The hardest technique needed to solve this grid without guessing, or
+    /// `None` if the known techniques aren't enough.
+    pub fn grade(&self) -> Option<Technique> {
+        let solve = self.logical_solve();
+        if solve.is_solved() {
+            solve.hardest()
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fehler::throws;
+    use rand::prelude::*;
+
+    use crate::grid::Grid;
+
+    use super::*;
+
+    #[test]
+    #[throws(Box<dyn std::error::Error>)]
+    fn logical_solve_easy() {
+        let grid = Grid::from_file("resources/test.sud")?;
+        let solve = grid.logical_solve();
+
+        assert!(solve.is_solved());
+        assert_eq!(Some(solve.grid.clone()), grid.solve());
+        assert!(solve.hardest() <= Some(Technique::HiddenSingle));
+    }
+
+    #[test]
+    fn logical_solve_stalls_without_guessing() {
+        let solve = Grid::empty().logical_solve();
+
+        assert!(solve.is_solved().not());
+        assert_eq!(Grid::empty().grade(), None);
+    }
+
+    #[test]
+    fn marks_follow_rules_beyond_the_units() {
+        let (one, two) = (Value::from(1), Value::from(2));
+        let mut grid = Grid::empty().with_anti_knight();
+        grid.spaces[0] = Space::Occupied(one);
+        let mut marks = Marks::new(grid);
+        assert!(marks.candidates[11].contains(one).not());
+        assert!(marks.candidates[12].contains(one));
+
+        marks.place(40, two);
+        assert!(marks.candidates[21].contains(two).not());
+        assert!(marks.candidates[12].contains(two));
+    }
+
+    #[test]
+    fn generate_requiring_technique() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let generated = Grid::generate_requiring(Technique::NakedPair, &mut rng).unwrap();
+
+        assert_eq!(generated.puzzle.grade(), Some(Technique::NakedPair));
+        let easier = Technique::iter()
+            .filter(|&t| t < Technique::NakedPair)
+            .collect::<Vec<Technique>>();
+        assert!(generated
+            .puzzle
+            .logical_solve_with(&easier)
+            .is_solved()
+            .not());
+        assert!(generated.puzzle.has_unique_solution());
+    }
+}
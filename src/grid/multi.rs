@@ -0,0 +1,488 @@
+use std::{collections::BTreeSet, fmt, ops::Not, str::FromStr};
+
+use crate::{candidates::Candidates, space::Space, value::Value};
+
+use super::{
+    constraint::{Constraint, Propagate, Unique},
+    Board, ParseGridError,
+};
+
+/// A puzzle made of several grids of the same size laid over one another so
+/// that they share whole boxes, such as a Samurai's five 9x9 grids, which
+/// all have to be solved at once.
+///
+/// Spaces are given by their (x, y) position across the whole puzzle, where
+/// some positions aren't in any grid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiGrid<const BW: usize = 3, const BH: usize = 3, const N: usize = 9> {
+    /// The top left space of each grid.
+    corners: Vec<(usize, usize)>,
+    width: usize,
+    height: usize,
+    /// Every position of the puzzle, row by row, where those outside every
+    /// grid are `None`.
+    spaces: Vec<Option<Space>>,
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> MultiGrid<BW, BH, N> {
+    /// An empty puzzle of grids with their top left spaces at `corners`,
+    /// which must line up with each other's boxes.
+    pub fn with_corners(corners: &[(usize, usize)]) -> Self {
+        assert!(corners.is_empty().not(), "a puzzle needs at least one grid");
+        assert!(
+            corners.iter().all(|&(x, y)| x % BW == 0 && y % BH == 0),
+            "grids must share whole boxes",
+        );
+
+        let width = corners.iter().map(|&(x, _)| x + N).max().unwrap_or(0);
+        let height = corners.iter().map(|&(_, y)| y + N).max().unwrap_or(0);
+        let mut spaces = vec![None; width * height];
+        for &(left, top) in corners.iter() {
+            for y in top..top + N {
+                for x in left..left + N {
+                    spaces[x + y * width] = Some(Space::Empty);
+                }
+            }
+        }
+
+        Self {
+            corners: corners.to_vec(),
+            width,
+            height,
+            spaces,
+        }
+    }
+
+    /// An empty Samurai puzzle: a grid at each corner, each sharing its
+    /// innermost box with a fifth grid in the middle.
+    pub fn samurai() -> Self {
+        let (across, down) = (2 * (N - BW), 2 * (N - BH));
+
+        Self::with_corners(&[
+            (0, 0),
+            (across, 0),
+            (across / 2, down / 2),
+            (0, down),
+            (across, down),
+        ])
+    }
+
+    /// The top left space of each grid.
+    pub fn corners(&self) -> &[(usize, usize)] {
+        &self.corners
+    }
+
+    /// How many spaces wide and tall the whole puzzle is.
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// The space at (x, y), or `None` if that isn't in any grid.
+    pub fn get(&self, x: usize, y: usize) -> Option<Space> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        self.spaces[x + y * self.width]
+    }
+
+    /// Put `value` in the space at (x, y), in every grid it's part of.
+    pub fn set(&mut self, x: usize, y: usize, value: Value) {
+        assert!(self.get(x, y).is_some(), "({}, {}) isn't in any grid", x, y);
+
+        self.spaces[x + y * self.width] = Some(Space::Occupied(value));
+    }
+
+    /// The number of givens across the whole puzzle, counting shared spaces
+    /// once.
+    pub fn clues(&self) -> usize {
+        self.spaces
+            .iter()
+            .filter(|space| matches!(space, Some(Space::Occupied(_))))
+            .count()
+    }
+
+    /// Each grid on its own, in the order of `corners`.
+    pub fn grids(&self) -> Vec<Board<BW, BH, N>> {
+        self.corners
+            .iter()
+            .map(|&(left, top)| {
+                let mut grid = Board::empty();
+                for i in 0..N * N {
+                    let (x, y) = (left + i % N, top + i / N);
+                    grid.spaces[i] = self.get(x, y).unwrap_or(Space::Empty);
+                }
+                grid
+            })
+            .collect()
+    }
+
+    /// Whether every grid is solved.
+    pub fn is_solved(&self) -> bool {
+        self.grids().iter().all(Board::is_solved)
+    }
+
+    /// Solve every grid at once, as their shared spaces tie them together.
+    pub fn solve(&self) -> Option<Self> {
+        self.solutions(1).pop()
+    }
+
+    /// Up to `limit` solutions, in no particular order.
+    pub fn solutions(&self, limit: usize) -> Vec<Self> {
+        let units = self.units();
+        let mut units_of = vec![vec![]; self.spaces.len()];
+        for (u, unit) in units.iter().enumerate() {
+            for &i in unit.spaces() {
+                units_of[i].push(u);
+            }
+        }
+
+        let mut web = Web {
+            candidates: self
+                .spaces
+                .iter()
+                .map(|space| match space {
+                    Some(_) => Candidates::all(N),
+                    None => Candidates::none(),
+                })
+                .collect(),
+            units: &units,
+            units_of: &units_of,
+        };
+        for (i, space) in self.spaces.iter().enumerate() {
+            if let Some(Space::Occupied(value)) = space {
+                if web.assign(i, *value).not() {
+                    return vec![];
+                }
+            }
+        }
+
+        let mut solutions = vec![];
+        web.solutions_into(limit, &mut solutions);
+        solutions
+            .into_iter()
+            .map(|candidates| Self {
+                spaces: candidates
+                    .into_iter()
+                    .map(|c| c.single().map(Space::Occupied))
+                    .collect(),
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// Count solutions, stopping once `limit` have been found.
+    pub fn count_solutions(&self, limit: usize) -> usize {
+        self.solutions(limit).len()
+    }
+
+    /// The rows, columns and boxes of every grid, where a box shared by two
+    /// grids is only counted once.
+    fn units(&self) -> Vec<Unique> {
+        let mut units = BTreeSet::new();
+        for &(left, top) in self.corners.iter() {
+            let index = |x: usize, y: usize| left + x + (top + y) * self.width;
+            for n in 0..N {
+                units.insert((0..N).map(|x| index(x, n)).collect::<Vec<usize>>());
+                units.insert((0..N).map(|y| index(n, y)).collect::<Vec<usize>>());
+                let (bx, by) = (n % (N / BW) * BW, n / (N / BW) * BH);
+                units.insert(
+                    (0..N)
+                        .map(|i| index(bx + i % BW, by + i / BW))
+                        .collect::<Vec<usize>>(),
+                );
+            }
+        }
+
+        units
+            .into_iter()
+            .map(|spaces| Unique::new(spaces, false))
+            .collect()
+    }
+}
+
+/// The candidates for every position of a multi-grid, where those outside
+/// every grid have none, propagated through the units of all its grids at
+/// once.
+#[derive(Clone)]
+struct Web<'a> {
+    candidates: Vec<Candidates>,
+    units: &'a [Unique],
+    units_of: &'a [Vec<usize>],
+}
+
+impl Propagate for Web<'_> {
+    fn candidates(&self, i: usize) -> Candidates {
+        self.candidates[i]
+    }
+
+    fn eliminate(&mut self, i: usize, value: Value) -> bool {
+        if self.candidates[i].remove(value).not() {
+            return true;
+        }
+        if self.candidates[i].is_empty() {
+            return false;
+        }
+
+        let (units, units_of) = (self.units, self.units_of);
+        units_of[i]
+            .iter()
+            .all(|&u| units[u].prune(self, Some((i, value))))
+    }
+}
+
+impl Web<'_> {
+    fn solutions_into(self, limit: usize, solutions: &mut Vec<Vec<Candidates>>) {
+        let i = (0..self.candidates.len())
+            .filter(|&i| self.candidates[i].len() > 1)
+            .min_by_key(|&i| self.candidates[i].len());
+        let i = match i {
+            Some(i) => i,
+            None => return solutions.push(self.candidates),
+        };
+        for value in self.candidates[i].iter() {
+            if solutions.len() >= limit {
+                return;
+            }
+            let mut guess = self.clone();
+            if guess.assign(i, value) {
+                guess.solutions_into(limit, solutions);
+            }
+        }
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> FromStr for MultiGrid<BW, BH, N> {
+    type Err = ParseGridError;
+
+    /// Read a line naming the top left space of each grid, like
+    /// `grids: r1c1 r3c3`, then a picture of the puzzle, a row of spaces to a
+    /// line, with `.` or `0` for an empty space, the value symbols for givens,
+    /// and a blank wherever there's no space. The grids must line up with
+    /// each other's boxes, and the picture must have a space wherever there's
+    /// one in a grid, and nowhere else.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().map(str::trim_end).skip_while(|l| l.is_empty());
+        let header = lines.next().unwrap_or_default();
+        let corners = header
+            .strip_prefix("grids:")
+            .ok_or(ParseGridError::InvalidLayout)?
+            .split_whitespace()
+            .map(|cell| corner(cell).ok_or_else(|| ParseGridError::InvalidCell(cell.into())))
+            .collect::<Result<Vec<(usize, usize)>, ParseGridError>>()?;
+        if corners.is_empty() || corners.iter().any(|&(x, y)| x % BW != 0 || y % BH != 0) {
+            return Err(ParseGridError::InvalidLayout);
+        }
+
+        let rows = lines.collect::<Vec<&str>>();
+        let rows = match rows.iter().rposition(|row| row.is_empty().not()) {
+            Some(last) => &rows[..=last],
+            None => &[][..],
+        };
+        let mut grid = Self::with_corners(&corners);
+        if rows.len() != grid.height {
+            return Err(ParseGridError::InvalidLayout);
+        }
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() > grid.width {
+                return Err(ParseGridError::InvalidLayout);
+            }
+            let mut row = row.chars().chain(std::iter::repeat(' '));
+            for x in 0..grid.width {
+                let c = row.next().unwrap_or(' ');
+                let space = match Space::from_symbol(c) {
+                    _ if c == ' ' => None,
+                    Some(Space::Occupied(v)) if usize::from(u8::from(v)) > N => {
+                        return Err(ParseGridError::InvalidSymbol(c))
+                    }
+                    Some(space) => Some(space),
+                    None => return Err(ParseGridError::InvalidSymbol(c)),
+                };
+                match (grid.get(x, y), space) {
+                    (Some(_), Some(space)) => grid.spaces[x + y * grid.width] = Some(space),
+                    (None, None) => {}
+                    _ => return Err(ParseGridError::InvalidLayout),
+                }
+            }
+        }
+
+        Ok(grid)
+    }
+}
+
+/// The (x, y) position of a space written like `r1c1`.
+fn corner(cell: &str) -> Option<(usize, usize)> {
+    let (row, column) = cell.strip_prefix('r')?.split_once('c')?;
+    let (row, column) = (row.parse::<usize>().ok()?, column.parse::<usize>().ok()?);
+
+    Some((column.checked_sub(1)?, row.checked_sub(1)?))
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> fmt::Display for MultiGrid<BW, BH, N> {
+    /// Write the grids and picture read by `from_str`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let corners = self
+            .corners
+            .iter()
+            .map(|&(x, y)| format!("r{}c{}", y + 1, x + 1))
+            .collect::<Vec<String>>();
+        writeln!(f, "grids: {}", corners.join(" "))?;
+        for row in self.spaces.chunks(self.width) {
+            let row = row
+                .iter()
+                .map(|space| match space {
+                    Some(Space::Occupied(v)) => v.symbol(),
+                    Some(Space::Empty) => '.',
+                    None => ' ',
+                })
+                .collect::<String>();
+            writeln!(f, "{}", row.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid4;
+
+    use super::*;
+
+    type Multi4 = MultiGrid<2, 2, 4>;
+
+    /// Two 4x4 grids sharing a box, which each have several solutions on
+    /// their own, but only one together.
+    const TWODOKU: &str = "\
+grids: r1c1 r3c3
+.23.
+...2
+.....2
+.3....
+  ..1.
+  ...4
+";
+
+    const SAMURAI: &str = "\
+grids: r1c1 r1c13 r7c7 r13c1 r13c13
+4538.7.9.   .....7...
+.......5.   ..5.9.4..
+...4.3...   ..8....2.
+.....8...   .....5679
+91.6.....   ...6...8.
+7.8....4.   9...4....
+.....2........1.5..6.
+2.5.6.7.......9.....1
+......2.5.7......8.5.
+      ....28.6.
+      ..16....7
+      .........
+.........7..6...5....
+..6........4.......8.
+..18.......2.....9..6
+..49.82..   ..2..8...
+.1....4.9   83.6.....
+...5....7   ...5....2
+.4....6..   5.....61.
+..96..83.   .4...7.5.
+.38.....4   1.7...9..
+";
+
+    #[test]
+    fn samurai_grids_share_their_inner_boxes() {
+        let samurai = MultiGrid::<3, 3, 9>::samurai();
+        assert_eq!(
+            samurai.corners(),
+            &[(0, 0), (12, 0), (6, 6), (0, 12), (12, 12)]
+        );
+        assert_eq!(samurai.size(), (21, 21));
+        assert_eq!(samurai.get(6, 6), Some(Space::Empty));
+        assert_eq!(samurai.get(9, 9), Some(Space::Empty));
+        assert_eq!(samurai.get(9, 0), None);
+        assert_eq!(samurai.get(21, 0), None);
+
+        let puzzle = SAMURAI.parse::<MultiGrid>().unwrap();
+        assert_eq!(puzzle.corners(), samurai.corners());
+        assert_eq!(puzzle.to_string(), SAMURAI);
+        let solution = puzzle.solve().unwrap();
+        assert!(solution.is_solved());
+        assert_eq!(puzzle.count_solutions(2), 1);
+    }
+
+    #[test]
+    fn solving_ties_the_grids_together() {
+        let twodoku = TWODOKU.parse::<Multi4>().unwrap();
+        assert_eq!(twodoku.corners(), &[(0, 0), (2, 2)]);
+        assert_eq!(twodoku.to_string(), TWODOKU);
+        assert_eq!(twodoku.clues(), 7);
+
+        let alone = twodoku
+            .grids()
+            .iter()
+            .map(|grid| grid.count_solutions(usize::MAX))
+            .collect::<Vec<usize>>();
+        assert_eq!(alone, vec![4, 6]);
+        let solutions = twodoku.solutions(usize::MAX);
+        assert_eq!(solutions.len(), 1);
+        assert!(solutions[0].is_solved());
+
+        // The shared box is the same in both grids.
+        let grids = solutions[0].grids();
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter().cloned() {
+            assert_eq!(grids[0].get(x + 2, y + 2), grids[1].get(x, y));
+        }
+    }
+
+    #[test]
+    fn every_box_is_drawn() {
+        let twodoku = TWODOKU.parse::<Multi4>().unwrap();
+        let styled = twodoku.to_text();
+        let lines = styled.lines().map(str::trim_end).collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "┌──────┬──────┐");
+        assert_eq!(lines[3], "├──────┼──────┼──────┐");
+        assert_eq!(lines[6], "└──────┼──────┼──────┤");
+        assert_eq!(lines[8], "       │ .  . │ .  4 │");
+        assert_eq!(lines[9], "       └──────┴──────┘");
+    }
+
+    #[test]
+    fn pictures_round_trip() {
+        let corners = [(0, 0), (6, 0), (0, 6), (6, 6)];
+        let mut puzzle = MultiGrid::<3, 3, 9>::with_corners(&corners);
+        puzzle.set(7, 7, Value::from(5));
+        let picture = puzzle.to_string();
+        assert!(picture.starts_with("grids: r1c1 r1c7 r7c1 r7c7\n"));
+
+        let read = picture.parse::<MultiGrid>().unwrap();
+        assert_eq!(read.corners(), &corners);
+        assert_eq!(read, puzzle);
+    }
+
+    #[test]
+    fn pictures_reject() {
+        let err = |picture: &str| picture.parse::<Multi4>().err();
+        let invalid = Some(ParseGridError::InvalidLayout);
+        assert_eq!(err(""), invalid);
+        assert_eq!(err("....\n....\n....\n...."), invalid);
+        assert_eq!(err("grids:\n....\n....\n....\n...."), invalid);
+        assert_eq!(err("grids: r1c2\n ....\n ....\n ....\n ...."), invalid);
+        assert_eq!(err("grids: r1c1\n...\n...\n...\n..."), invalid);
+        assert_eq!(err("grids: r1c1\n....\n....\n....\n....\n.."), invalid);
+        assert_eq!(err("grids: r1c1\n....\n.. .\n....\n...."), invalid);
+        assert_eq!(
+            err("grids: r0c1\n....\n....\n....\n...."),
+            Some(ParseGridError::InvalidCell("r0c1".into()))
+        );
+        assert_eq!(
+            err("grids: r1c1\n....\n.5..\n....\n...."),
+            Some(ParseGridError::InvalidSymbol('5'))
+        );
+        assert_eq!(
+            "grids: r1c1\n1...\n....\n....\n...."
+                .parse::<Multi4>()
+                .map(|m| m.grids()),
+            Ok(vec!["1...............".parse::<Grid4>().unwrap()])
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use std::{ops::Not, sync::Arc};
+
+use rand::prelude::*;
+
+use crate::{
+    candidates::Candidates,
+    solver::{Search, SolveOptions, SolveReport},
+    space::Space,
+    value::Value,
+};
+
+use super::{
+    constraint::{Constraint, Propagate, Rules},
+    Board, Cells, Layout,
+};
+
+/// The candidates for every space, where a space with a single candidate is
+/// treated as filled. Whenever a space loses a candidate, each constraint
+/// covering it prunes in turn, which is how a unit hands a filled space's
+/// value to the rest of it, or a value with one place left to that place.
+#[derive(Clone)]
+pub(super) struct Propagator<const BW: usize, const BH: usize, const N: usize> {
+    // Kept on the heap, as the search recurses once per guess and would
+    // otherwise need a copy of every candidate on the stack each time.
+    candidates: Vec<Candidates>,
+    layout: Arc<Layout>,
+    rules: Arc<Rules>,
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Propagate for Propagator<BW, BH, N> {
+    fn candidates(&self, i: usize) -> Candidates {
+        self.candidates[i]
+    }
+
+    fn eliminate(&mut self, i: usize, value: Value) -> bool {
+        if self.candidates[i].remove(value).not() {
+            return true;
+        }
+        if self.candidates[i].is_empty() {
+            return false;
+        }
+
+        let layout = Arc::clone(&self.layout);
+        for &u in layout.units_of[i].iter() {
+            if layout.units[u].prune(self, Some((i, value))).not() {
+                return false;
+            }
+        }
+        if self.rules.of(i).is_empty() {
+            return true;
+        }
+        let rules = Arc::clone(&self.rules);
+        rules
+            .of(i)
+            .iter()
+            .all(|&r| rules.get(r).prune(self, Some((i, value))))
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Propagator<BW, BH, N> {
+    /// Propagate the givens of `grid`, or `None` if they contradict.
+    pub(super) fn new(grid: &Board<BW, BH, N>) -> Option<Self> {
+        let mut propagator = Self {
+            candidates: vec![Candidates::all(N); N * N],
+            layout: Arc::clone(&grid.layout),
+            rules: Arc::clone(&grid.rules),
+        };
+        for (i, space) in grid.spaces.iter().enumerate() {
+            if let Space::Occupied(value) = space {
+                if propagator.assign(i, *value).not() {
+                    return None;
+                }
+            }
+        }
+        let rules = Arc::clone(&grid.rules);
+        if rules
+            .iter()
+            .all(|rule| rule.prune(&mut propagator, None))
+            .not()
+        {
+            return None;
+        }
+
+        Some(propagator)
+    }
+
+    /// The undecided space with the fewest candidates, if any are left.
+    fn most_constrained(&self) -> Option<usize> {
+        (0..N * N)
+            .filter(|&i| self.candidates[i].len() > 1)
+            .min_by_key(|&i| self.candidates[i].len())
+    }
+
+    fn search(self, search: &mut Search) -> Option<Self> {
+        if search.visit().not() {
+            return None;
+        }
+
+        let i = match self.most_constrained() {
+            Some(i) => i,
+            None => return Some(self),
+        };
+        for value in self.candidates[i].iter() {
+            search.guesses += 1;
+            let mut guess = self.clone();
+            if guess.assign(i, value) {
+                if let Some(solution) = guess.search(search) {
+                    return Some(solution);
+                }
+                if search.is_stopped() {
+                    return None;
+                }
+            }
+            search.backtracks += 1;
+        }
+
+        None
+    }
+
+    /// Search with the values of each guess in a random order, for a random
+    /// solution.
+    pub(super) fn randomised_solution<R: Rng + ?Sized>(
+        self,
+        rng: &mut R,
+    ) -> Option<Board<BW, BH, N>> {
+        let i = match self.most_constrained() {
+            Some(i) => i,
+            None => return Some(self.to_grid()),
+        };
+        let mut values = self.candidates[i].iter().collect::<Vec<Value>>();
+        values.shuffle(rng);
+        for value in values {
+            let mut guess = self.clone();
+            if guess.assign(i, value) {
+                if let Some(solution) = guess.randomised_solution(rng) {
+                    return Some(solution);
+                }
+            }
+        }
+
+        None
+    }
+
+    pub(super) fn solutions(self, limit: usize) -> Vec<Board<BW, BH, N>> {
+        let mut solutions = vec![];
+        self.solutions_into(limit, &mut solutions);
+
+        solutions
+    }
+
+    fn solutions_into(self, limit: usize, solutions: &mut Vec<Board<BW, BH, N>>) {
+        let i = match self.most_constrained() {
+            Some(i) => i,
+            None => {
+                solutions.push(self.to_grid());
+                return;
+            }
+        };
+        for value in self.candidates[i].iter() {
+            let mut guess = self.clone();
+            if guess.assign(i, value) {
+                guess.solutions_into(limit, solutions);
+                if solutions.len() >= limit {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn to_grid(&self) -> Board<BW, BH, N> {
+        let mut grid = Board {
+            spaces: Cells::filled(Space::Empty),
+            layout: Arc::clone(&self.layout),
+            rules: Arc::clone(&self.rules),
+        };
+        for (i, candidates) in self.candidates.iter().enumerate() {
+            if let Some(value) = candidates.single() {
+                grid.spaces[i] = Space::Occupied(value);
+            }
+        }
+
+        grid
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    pub fn propagation_solve(self) -> Option<Self> {
+        self.propagation_solve_with(&SolveOptions::default())
+            .solution
+    }
+
+    /// Solve by propagating constraints, only guessing when propagation
+    /// stalls, and then on the space with the fewest candidates.
+    pub fn propagation_solve_with(self, options: &SolveOptions) -> SolveReport<BW, BH, N> {
+        let mut search = Search::new(options);
+        let solution = Propagator::new(&self)
+            .and_then(|propagator| propagator.search(&mut search))
+            .map(|propagator| propagator.to_grid());
+
+        search.report(solution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use crate::grid::Grid;
+
+    #[test_case(
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+    )]
+    #[test_case(
+        "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......"
+    )]
+    #[test_case(
+        "................................................................................."
+    )]
+    fn propagation_solves(puzzle: &str) {
+        let grid: Grid = puzzle.parse().unwrap();
+        let solution = grid.clone().propagation_solve().unwrap();
+
+        assert!(solution.is_solved());
+        for (given, solved) in grid.spaces.iter().zip(solution.spaces.iter()) {
+            if given.is_occupied() {
+                assert_eq!(given, solved);
+            }
+        }
+    }
+
+    #[test]
+    fn propagation_rejects_contradictions() {
+        let grid: Grid = "11".parse().unwrap();
+
+        assert_eq!(grid.clone().propagation_solve(), None);
+        assert_eq!(grid.count_solutions(2), 0);
+    }
+
+    #[test]
+    fn count_solutions_stops_at_limit() {
+        assert_eq!(Grid::empty().count_solutions(3), 3);
+    }
+}
@@ -0,0 +1,111 @@
+use std::ops::Not;
+
+use crate::{space::Space, value::Value};
+
+use super::Board;
+
+/// How many solutions to sample when choosing which space to give next.
+const SAMPLES: usize = 50;
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Find a minimal set of extra givens that makes the solution unique:
+    /// without any one of them, the puzzle has more than one solution again.
+    /// Returns `None` if the grid has no solution at all, and no givens if
+    /// its solution is already unique.
+    pub fn disambiguate(&self) -> Option<Vec<((u8, u8), Value)>> {
+        let target = self.solutions(1).into_iter().next()?;
+
+        let mut puzzle = self.clone();
+        let mut added = vec![];
+        loop {
+            let solutions = puzzle.solutions(SAMPLES);
+            if solutions.len() <= 1 {
+                break;
+            }
+
+            // Give the space where the most sampled solutions disagree with
+            // the target, to rule out as many of them as possible at once.
+            let i = (0..N * N)
+                .filter(|&i| puzzle.spaces[i].is_occupied().not())
+                .max_by_key(|&i| {
+                    solutions
+                        .iter()
+                        .filter(|s| s.spaces[i] != target.spaces[i])
+                        .count()
+                })
+                .unwrap();
+            puzzle.spaces[i] = target.spaces[i];
+            added.push(i);
+        }
+
+        // Later givens can make earlier ones redundant, so drop any that
+        // aren't needed after all.
+        for &i in added.clone().iter() {
+            puzzle.spaces[i] = Space::Empty;
+            if puzzle.has_unique_solution() {
+                added.retain(|&a| a != i);
+            } else {
+                puzzle.spaces[i] = target.spaces[i];
+            }
+        }
+
+        let givens = added
+            .into_iter()
+            .map(|i| match target.spaces[i] {
+                Space::Occupied(value) => (Self::position(i), value),
+                Space::Empty => unreachable!("solutions are complete"),
+            })
+            .collect();
+
+        Some(givens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+
+    use super::*;
+
+    #[test]
+    fn unique_grid_needs_nothing() {
+        let grid: Grid =
+            "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+                .parse()
+                .unwrap();
+
+        assert_eq!(grid.disambiguate(), Some(vec![]));
+    }
+
+    #[test]
+    fn unsolvable_grid_cannot_be_repaired() {
+        let grid: Grid = "11".parse().unwrap();
+
+        assert_eq!(grid.disambiguate(), None);
+    }
+
+    #[test]
+    fn repaired_grid_is_unique_and_minimal() {
+        // Clearing a few more givens from a unique puzzle leaves it with
+        // several solutions.
+        let grid: Grid =
+            "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82......................5.1.3.."
+                .parse()
+                .unwrap();
+        assert!(grid.count_solutions(2) > 1);
+
+        let givens = grid.disambiguate().unwrap();
+        assert!(givens.is_empty().not());
+
+        let mut repaired = grid;
+        for &((x, y), value) in givens.iter() {
+            repaired.set(x, y, value);
+        }
+        assert!(repaired.has_unique_solution());
+        for &((x, y), _) in givens.iter() {
+            let mut relaxed = repaired.clone();
+            relaxed.set_empty(x, y);
+            assert!(relaxed.has_unique_solution().not());
+        }
+    }
+}
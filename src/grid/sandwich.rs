@@ -0,0 +1,268 @@
+use std::ops::Not;
+
+use crate::{candidates::Candidates, space::Space, value::Value};
+
+use super::{
+    constraint::{Constraint, Hint, Propagate},
+    killer::add_combinations,
+    Board, ParseGridError,
+};
+
+/// Which way a sandwich clue reads.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Line {
+    Row(usize),
+    Column(usize),
+}
+
+/// A sandwich clue: the sum of the values between the lowest and the highest
+/// value in a row or column, which are the crusts.
+struct Sandwich {
+    line: Line,
+    /// The spaces of the row or column, in order.
+    spaces: Vec<usize>,
+    sum: usize,
+    size: usize,
+    /// For each number of spaces between the crusts, every set of distinct
+    /// values that could fill them and add up to the sum.
+    fillings: Vec<Vec<Candidates>>,
+}
+
+impl Sandwich {
+    fn new(line: Line, sum: usize, size: usize) -> Self {
+        let spaces = match line {
+            Line::Row(y) => (0..size).map(|x| x + y * size).collect(),
+            Line::Column(x) => (0..size).map(|y| x + y * size).collect(),
+        };
+        let fillings = (0..=size - 2)
+            .map(|count| {
+                let mut fillings = vec![];
+                add_combinations(2, size - 1, count, sum, Candidates::none(), &mut fillings);
+                fillings
+            })
+            .collect();
+
+        Self {
+            line,
+            spaces,
+            sum,
+            size,
+            fillings,
+        }
+    }
+
+    fn crusts(&self) -> (Value, Value) {
+        (Value::from(1), Value::from(self.size as u8))
+    }
+}
+
+impl Constraint for Sandwich {
+    fn spaces(&self) -> &[usize] {
+        &self.spaces
+    }
+
+    /// Try the crusts in every pair of places they could go, either way
+    /// round, with every filling that fits between them. Each space keeps
+    /// only the candidates one of those allows, with the filling's values
+    /// between the crusts and none of them outside.
+    fn prune(&self, grid: &mut dyn Propagate, _removed: Option<(usize, Value)>) -> bool {
+        let (low, high) = self.crusts();
+        let crusts = [low, high].iter().cloned().collect::<Candidates>();
+        let candidates = self
+            .spaces
+            .iter()
+            .map(|&i| grid.candidates(i))
+            .collect::<Vec<Candidates>>();
+        let crust_at = |k: usize| candidates[k].intersection(crusts).is_empty().not();
+
+        let mut allowed = vec![Candidates::none(); self.size];
+        for a in (0..self.size).filter(|&a| crust_at(a)) {
+            for b in (a + 1..self.size).filter(|&b| crust_at(b)) {
+                for &(first, last) in [(low, high), (high, low)].iter() {
+                    if candidates[a].contains(first).not() || candidates[b].contains(last).not() {
+                        continue;
+                    }
+                    for &filling in self.fillings[b - a - 1].iter() {
+                        let options = (0..self.size)
+                            .map(|k| match k {
+                                _ if k == a => Some(first).into_iter().collect(),
+                                _ if k == b => Some(last).into_iter().collect(),
+                                _ if a < k && k < b => candidates[k].intersection(filling),
+                                _ => candidates[k].difference(filling.union(crusts)),
+                            })
+                            .collect::<Vec<Candidates>>();
+                        let covered = options[a + 1..b]
+                            .iter()
+                            .fold(Candidates::none(), |acc, &o| acc.union(o));
+                        if options.iter().any(|o| o.is_empty()) || covered != filling {
+                            continue;
+                        }
+                        for (allowed, option) in allowed.iter_mut().zip(options) {
+                            *allowed = allowed.union(option);
+                        }
+                    }
+                }
+            }
+        }
+
+        for (&i, allowed) in self.spaces.iter().zip(allowed) {
+            let ruled_out = grid.candidates(i).difference(allowed);
+            if ruled_out.iter().all(|value| grid.eliminate(i, value)).not() {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Whether the values between the crusts could still add up to the sum
+    /// with `value` in space `i`, once both crusts are placed.
+    fn allows(&self, spaces: &[Space], i: usize, value: Value) -> bool {
+        let (low, high) = self.crusts();
+        let value_at = |p: usize| match spaces[p] {
+            _ if p == i => Some(value),
+            Space::Occupied(v) => Some(v),
+            Space::Empty => None,
+        };
+        let find = |crust| self.spaces.iter().position(|&p| value_at(p) == Some(crust));
+        let (a, b) = match (find(low), find(high)) {
+            (Some(a), Some(b)) => (a.min(b), a.max(b)),
+            _ => return true,
+        };
+
+        let between = &self.spaces[a + 1..b];
+        let placed = between
+            .iter()
+            .filter_map(|&p| value_at(p))
+            .map(|v| usize::from(u8::from(v)))
+            .sum::<usize>();
+        let empty = between.iter().filter(|&&p| value_at(p).is_none()).count();
+
+        placed + empty * 2 <= self.sum && self.sum <= placed + empty * (self.size - 1)
+    }
+
+    fn holds(&self, spaces: &[Space]) -> bool {
+        let (low, high) = self.crusts();
+        let find = |crust| {
+            self.spaces
+                .iter()
+                .position(|&p| spaces[p] == Space::Occupied(crust))
+        };
+        let (a, b) = match (find(low), find(high)) {
+            (Some(a), Some(b)) => (a.min(b), a.max(b)),
+            _ => return false,
+        };
+        let sum = self.spaces[a + 1..b]
+            .iter()
+            .map(|&p| match spaces[p] {
+                Space::Occupied(v) => usize::from(u8::from(v)),
+                Space::Empty => 0,
+            })
+            .sum::<usize>();
+
+        sum == self.sum
+    }
+
+    fn hints(&self) -> Vec<Hint> {
+        let label = self.sum.to_string();
+        match self.line {
+            Line::Row(y) => vec![Hint::RowClue(y, label)],
+            Line::Column(x) => vec![Hint::ColumnClue(x, label)],
+        }
+    }
+
+    fn to_line(&self) -> Option<String> {
+        let line = match self.line {
+            Line::Row(y) => format!("r{}", y + 1),
+            Line::Column(x) => format!("c{}", x + 1),
+        };
+
+        Some(format!("sandwich:{}={}", line, self.sum))
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Add a sandwich clue written like `r1=12` or `c3=0`: the sum of the
+    /// values between the lowest and highest value, 1 and `N`, in that row
+    /// or column, counting from 1.
+    pub fn with_sandwich(self, clue: &str) -> Result<Self, ParseGridError> {
+        let invalid = || ParseGridError::InvalidClue(clue.trim().into());
+        let (line, sum) = clue.split_once('=').ok_or_else(invalid)?;
+        let mut line = line.trim().chars();
+        let kind = line.next().map(|c| c.to_ascii_lowercase());
+        let number = line
+            .as_str()
+            .parse::<usize>()
+            .ok()
+            .filter(|n| (1..=N).contains(n))
+            .ok_or_else(invalid)?;
+        let line = match kind {
+            Some('r') => Line::Row(number - 1),
+            Some('c') => Line::Column(number - 1),
+            _ => return Err(invalid()),
+        };
+        let sum = sum.trim().parse::<usize>().map_err(|_| invalid())?;
+
+        let sandwich = Sandwich::new(line, sum, N);
+        if sandwich.fillings.iter().all(Vec::is_empty) {
+            return Err(invalid());
+        }
+
+        Ok(self.with_constraint(sandwich))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fehler::throws;
+    use test_case::test_case;
+
+    use crate::grid::{Grid, Grid4};
+
+    use super::*;
+
+    /// A sandwich clue for every row and column of a solution, which pins
+    /// it down along with a single given.
+    const SANDWICH: &str = concat!(
+        "1...............",
+        ";sandwich:r1=5;sandwich:r2=0;sandwich:r3=0;sandwich:r4=0",
+        ";sandwich:c1=5;sandwich:c2=3;sandwich:c3=0;sandwich:c4=2",
+    );
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn sandwiches_pin_down_the_solution() {
+        let grid: Grid4 = SANDWICH.parse()?;
+        assert_eq!(grid.to_line(), SANDWICH);
+
+        let solution = grid.clone().solve().unwrap();
+        assert!(solution.is_solved());
+        assert_eq!(grid.count_solutions(2), 1);
+        assert_eq!(grid.clone().backtracking_solve(), Some(solution));
+    }
+
+    #[test]
+    #[throws(ParseGridError)]
+    fn sandwich_clues_are_drawn_outside() {
+        let grid = Grid4::empty()
+            .with_sandwich("r2=5")?
+            .with_sandwich("c3=0")?;
+        let styled = grid.to_string();
+        let lines = styled.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "           0     ");
+        assert_eq!(lines[1], "  ┌─────────────┐");
+        assert_eq!(lines[2], "  │ .  . ┆ .  . │");
+        assert_eq!(lines[3], "5 │ .  . ┆ .  . │");
+    }
+
+    #[test_case("r1", ParseGridError::InvalidClue("r1".into()) ; "no sum")]
+    #[test_case("r10=5", ParseGridError::InvalidClue("r10=5".into()) ; "outside")]
+    #[test_case("x1=5", ParseGridError::InvalidClue("x1=5".into()) ; "not a line")]
+    #[test_case("é1=5", ParseGridError::InvalidClue("é1=5".into()) ; "not ascii")]
+    #[test_case("=5", ParseGridError::InvalidClue("=5".into()) ; "no line")]
+    #[test_case("c1=36", ParseGridError::InvalidClue("c1=36".into()) ; "too big")]
+    fn sandwiches_reject(clue: &str, expected: ParseGridError) {
+        assert_eq!(Grid::empty().with_sandwich(clue).err(), Some(expected));
+    }
+}
@@ -1,71 +1,372 @@
+use std::{collections::HashMap, ops::Not};
+
 use crossterm::style::{style, Attribute, Color, StyledContent};
 
 use crate::space::Space;
 
-use super::Grid;
+use super::{Board, Hint, MultiGrid};
 
 const TOP_LEFT: char = '┌';
 const TOP_RIGHT: char = '┐';
 const BOTTOM_LEFT: char = '└';
 const BOTTOM_RIGHT: char = '┘';
 const OUTSIDE_ACROSS: char = '─';
-const INSIDE_ACROSS: char = '┄';
 const OUTSIDE_DOWN: char = '│';
-const INSIDE_DOWN: char = '┆';
-const INSIDE_INTERSECTION: char = '┼';
 
-impl Grid {
+/// The background of shaded spaces, such as the diagonals of a Sudoku-X
+/// grid.
+const SHADE: Color = Color::Rgb {
+    r: 40,
+    g: 40,
+    b: 64,
+};
+
+/// The colour of the lines joining up spaces, such as thermometers and
+/// arrows.
+const LINK: Color = Color::Rgb {
+    r: 200,
+    g: 140,
+    b: 60,
+};
+
+/// The characters for the borders between regions inside the grid.
+struct Borders {
+    down: char,
+    across: char,
+    /// Where borders meet, indexed by whether there are arms going up, down,
+    /// left and right, as bits from the highest.
+    junctions: [char; 16],
+}
+
+impl Borders {
+    fn junction(&self, arms: [bool; 4]) -> char {
+        let arms = arms
+            .iter()
+            .fold(0, |bits, &arm| bits << 1 | usize::from(arm));
+
+        self.junctions[arms]
+    }
+}
+
+/// Light, dotted borders between boxes.
+#[rustfmt::skip]
+const BOX_BORDERS: Borders = Borders {
+    down: '┆',
+    across: '┄',
+    junctions: [
+        ' ', '┄', '┄', '┄', '┆', '┌', '┐', '┬',
+        '┆', '└', '┘', '┴', '┆', '├', '┤', '┼',
+    ],
+};
+
+/// Heavy borders between regions, for grids with a gap between most spaces,
+/// where the regions need to stand out from the gaps inside them.
+#[rustfmt::skip]
+const HEAVY_BORDERS: Borders = Borders {
+    down: '┃',
+    across: '━',
+    junctions: [
+        ' ', '━', '━', '━', '┃', '┏', '┓', '┳',
+        '┃', '┗', '┛', '┻', '┃', '┣', '┫', '╋',
+    ],
+};
+
+/// Solid borders around every box, for puzzles of overlapping grids, where
+/// the boxes of each grid are all that show where it lies.
+#[rustfmt::skip]
+const FRAME_BORDERS: Borders = Borders {
+    down: '│',
+    across: '─',
+    junctions: [
+        ' ', '─', '─', '─', '│', '┌', '┐', '┬',
+        '│', '└', '┘', '┴', '│', '├', '┤', '┼',
+    ],
+};
+
+/// Dashed outlines, such as around killer cages, wherever they don't run
+/// along the border of a region.
+#[rustfmt::skip]
+const OUTLINE_BORDERS: Borders = Borders {
+    down: '╎',
+    across: '╌',
+    junctions: [
+        ' ', '╌', '╌', '╌', '╎', '┌', '┐', '┬',
+        '╎', '└', '┘', '┴', '╎', '├', '┤', '┼',
+    ],
+};
+
+/// The marks joining up the spaces along lines, by the (x, y) position of the
+/// space they follow: in the gap to its right, in the gap below it, or in the
+/// corner between those gaps.
+#[derive(Default)]
+struct Links {
+    across: HashMap<(usize, usize), char>,
+    down: HashMap<(usize, usize), char>,
+    corner: HashMap<(usize, usize), char>,
+}
+
+impl Links {
+    /// Join up `spaces` in a grid `size` spaces wide, pointing an arrowhead
+    /// at the last of them if it's an `arrow`.
+    fn join(&mut self, spaces: &[usize], size: usize, arrow: bool) {
+        for (n, pair) in spaces.windows(2).enumerate() {
+            let head = arrow && n + 2 == spaces.len();
+            let (from, to) = (
+                (pair[0] % size, pair[0] / size),
+                (pair[1] % size, pair[1] / size),
+            );
+            let after = (from.0.min(to.0), from.1.min(to.1));
+            let (right, down) = (to.0 > from.0, to.1 > from.1);
+            if from.1 == to.1 {
+                let mark = match (head, right) {
+                    (false, _) => '─',
+                    (true, true) => '→',
+                    (true, false) => '←',
+                };
+                self.across.insert(after, mark);
+            } else if from.0 == to.0 {
+                let mark = match (head, down) {
+                    (false, _) => '│',
+                    (true, true) => '↓',
+                    (true, false) => '↑',
+                };
+                self.down.insert(after, mark);
+            } else {
+                let mark = match (head, right, down) {
+                    (false, true, true) | (false, false, false) => '╲',
+                    (false, _, _) => '╱',
+                    (true, true, true) => '↘',
+                    (true, true, false) => '↗',
+                    (true, false, true) => '↙',
+                    (true, false, false) => '↖',
+                };
+                // Lines crossing diagonally share the corner.
+                let crossed = match self.corner.get(&after) {
+                    Some(&other) if other != mark => '╳',
+                    _ => mark,
+                };
+                self.corner.insert(after, crossed);
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.across.is_empty() && self.down.is_empty() && self.corner.is_empty()
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
     pub fn to_styled(&self) -> Vec<StyledContent<String>> {
+        self.to_styled_with(&HashMap::new())
+    }
+
+    /// Style the grid with the spaces at the given (x, y) positions picked
+    /// out in a background colour.
+    pub fn to_styled_with(
+        &self,
+        highlights: &HashMap<(u8, u8), Color>,
+    ) -> Vec<StyledContent<String>> {
+        let layout = &self.layout;
+        let region = |x: usize, y: usize| layout.region_of[x + y * N];
+        let between_across = |x, y| region(x, y) != region(x + 1, y);
+        let between_down = |x, y| region(x, y) != region(x, y + 1);
+
+        let mut shaded = vec![false; N * N];
+        let mut circled = vec![false; N * N];
+        let mut outline_of = vec![None; N * N];
+        let mut labels = HashMap::new();
+        let mut links = Links::default();
+        let mut row_clues = HashMap::new();
+        let mut column_clues = HashMap::new();
+        let mut marks_across = HashMap::new();
+        let mut marks_down = HashMap::new();
+        for (o, hint) in self.constraints().flat_map(|c| c.hints()).enumerate() {
+            match hint {
+                Hint::Shade(spaces) => {
+                    for i in spaces {
+                        shaded[i] = true;
+                    }
+                }
+                Hint::Outline(spaces, label) => {
+                    for &i in spaces.iter() {
+                        outline_of[i] = Some(o);
+                    }
+                    if let Some(&first) = spaces.iter().min() {
+                        labels.insert(first, label);
+                    }
+                }
+                Hint::Circle(i) => circled[i] = true,
+                Hint::Line(spaces) => links.join(&spaces, N, false),
+                Hint::Arrow(spaces) => links.join(&spaces, N, true),
+                Hint::RowClue(y, label) => {
+                    row_clues.insert(y, label);
+                }
+                Hint::ColumnClue(x, label) => {
+                    column_clues.insert(x, label);
+                }
+                Hint::Mark(a, b, symbol) => {
+                    let first = a.min(b);
+                    let marks = if a.max(b) == first + 1 {
+                        &mut marks_across
+                    } else {
+                        &mut marks_down
+                    };
+                    marks.insert((first % N, first / N), symbol);
+                }
+            }
+        }
+        let outlined = outline_of.iter().any(Option::is_some);
+        let marked = marks_across.is_empty().not() || marks_down.is_empty().not();
+        let spaced = outlined || marked || links.is_empty().not();
+        let outline = |x: usize, y: usize| outline_of[x + y * N];
+        let outline_across = |x, y| outline(x, y) != outline(x + 1, y);
+        let outline_down = |x, y| outline(x, y) != outline(x, y + 1);
+        let borders = if self.is_jigsaw() || spaced {
+            &HEAVY_BORDERS
+        } else {
+            &BOX_BORDERS
+        };
+        // Each outline's label goes in the border above its first space.
+        let label_above = |x: usize, y: usize| {
+            let label = labels.get(&(x + y * N))?;
+            Some(style(format!("{:<3.3}", label)))
+        };
+
+        // Leave a gap for a border between two columns wherever a region
+        // boundary runs between them, and likewise for rows, or everywhere
+        // when there are outlines, lines or marks to draw. Each space is three
+        // characters wide.
+        let gap_after_column = (0..N - 1)
+            .map(|x| spaced || (0..N).any(|y| between_across(x, y)))
+            .collect::<Vec<bool>>();
+        let gap_after_row = (0..N - 1)
+            .map(|y| spaced || (0..N).any(|x| between_down(x, y)))
+            .collect::<Vec<bool>>();
+        let link = |mark: &char| style(mark.to_string()).with(LINK);
+        let mark = |symbol: &char| style(symbol.to_string()).attribute(Attribute::Bold);
+        let across = N * 3 + gap_after_column.iter().filter(|&&gap| gap).count();
+        // Row clues go in a margin down the left, wide enough for the
+        // longest of them, and column clues on a line of their own above.
+        let width = row_clues.values().map(String::len).max();
+        let margin = |y: Option<usize>| {
+            let label = y.and_then(|y| row_clues.get(&y)).map_or("", String::as_str);
+            style(width.map_or_else(String::new, |width| format!("{:>1$} ", label, width)))
+        };
         let mut val = vec![];
 
+        if column_clues.is_empty().not() {
+            val.push(margin(None));
+            val.push(style(" ".to_string()));
+            for x in 0..N {
+                let label = column_clues.get(&x).map_or("", String::as_str);
+                val.push(style(format!("{:^3}", label)));
+                if gap_after_column.get(x) == Some(&true) {
+                    val.push(style(" ".to_string()));
+                }
+            }
+            val.push(style(" \n".to_string()));
+        }
+
+        val.push(margin(None));
         val.push(style(TOP_LEFT.to_string()));
-        for _ in 0..29 {
-            val.push(style(OUTSIDE_ACROSS.to_string()));
+        for x in 0..N {
+            let edge = style(OUTSIDE_ACROSS.to_string().repeat(3));
+            val.push(label_above(x, 0).unwrap_or(edge));
+            if gap_after_column.get(x) == Some(&true) {
+                val.push(style(OUTSIDE_ACROSS.to_string()));
+            }
         }
         val.push(style(TOP_RIGHT.to_string()));
         val.push(style("\n".to_string()));
 
-        for y in 0..9 {
+        for y in 0..N {
+            val.push(margin(Some(y)));
             val.push(style(OUTSIDE_DOWN.to_string()));
-            for x in 0..9 {
-                let space = self.get(x, y);
+            for x in 0..N {
+                let space = self.get(x as u8, y as u8);
+                let (open, close) = if circled[x + y * N] {
+                    ('(', ')')
+                } else {
+                    (' ', ' ')
+                };
+                let shown = format!("{}{}{}", open, space, close);
                 let styled = match space {
-                    Space::Occupied(v) => style(format!(" {} ", v))
-                        .with(v.color())
-                        .attribute(Attribute::Bold),
-                    Space::Empty => style(format!(" {} ", space)).with(Color::Grey),
+                    Space::Occupied(v) => style(shown).with(v.color()).attribute(Attribute::Bold),
+                    Space::Empty => style(shown).with(Color::Grey),
+                };
+                let styled = match highlights.get(&(x as u8, y as u8)) {
+                    Some(&color) => styled.on(color),
+                    None if shaded[x + y * N] => styled.on(SHADE),
+                    None => styled,
                 };
                 val.push(styled);
-                if x == 8 {
-                    break;
-                }
-                if (x + 1) % 3 == 0 {
-                    val.push(style(INSIDE_DOWN.to_string()));
+                if gap_after_column.get(x) == Some(&true) {
+                    let border = if between_across(x, y) {
+                        borders.down
+                    } else if outline_across(x, y) {
+                        OUTLINE_BORDERS.down
+                    } else {
+                        ' '
+                    };
+                    let border = style(border.to_string());
+                    let border = links.across.get(&(x, y)).map_or(border, link);
+                    val.push(marks_across.get(&(x, y)).map_or(border, mark));
                 }
             }
             val.push(style(OUTSIDE_DOWN.to_string()));
             val.push(style('\n'.to_string()));
-            if y == 8 {
-                break;
+            if gap_after_row.get(y) != Some(&true) {
+                continue;
             }
-            if (y + 1) % 3 == 0 {
-                val.push(style(OUTSIDE_DOWN.to_string()));
-                for _ in 0..2 {
-                    for _ in 0..9 {
-                        val.push(style(INSIDE_ACROSS.to_string()));
-                    }
-                    val.push(style(INSIDE_INTERSECTION.to_string()));
-                }
-                for _ in 0..9 {
-                    val.push(style(INSIDE_ACROSS.to_string()));
+
+            val.push(margin(None));
+            val.push(style(OUTSIDE_DOWN.to_string()));
+            for x in 0..N {
+                let border = if between_down(x, y) {
+                    borders.across
+                } else if outline_down(x, y) {
+                    OUTLINE_BORDERS.across
+                } else {
+                    ' '
+                };
+                // A mark sits on the border, between two pieces of it.
+                let marked = |symbol: &char| {
+                    style(format!("{}{}{}", border, symbol, border)).attribute(Attribute::Bold)
+                };
+                let styled =
+                    label_above(x, y + 1).unwrap_or_else(|| style(border.to_string().repeat(3)));
+                let down = links.down.get(&(x, y));
+                let styled = down.map_or(styled, |mark| style(format!(" {} ", mark)).with(LINK));
+                val.push(marks_down.get(&(x, y)).map_or(styled, marked));
+                if gap_after_column.get(x) == Some(&true) {
+                    let regions = [
+                        between_across(x, y),
+                        between_across(x, y + 1),
+                        between_down(x, y),
+                        between_down(x + 1, y),
+                    ];
+                    let outlines = [
+                        outline_across(x, y),
+                        outline_across(x, y + 1),
+                        outline_down(x, y),
+                        outline_down(x + 1, y),
+                    ];
+                    let meeting = if regions.iter().any(|&arm| arm) {
+                        borders.junction(regions)
+                    } else {
+                        OUTLINE_BORDERS.junction(outlines)
+                    };
+                    let meeting = style(meeting.to_string());
+                    val.push(links.corner.get(&(x, y)).map_or(meeting, link));
                 }
-                val.push(style(OUTSIDE_DOWN.to_string()));
-                val.push(style("\n".to_string()));
             }
+            val.push(style(OUTSIDE_DOWN.to_string()));
+            val.push(style("\n".to_string()));
         }
 
+        val.push(margin(None));
         val.push(style(BOTTOM_LEFT.to_string()));
-        for _ in 0..29 {
+        for _ in 0..across {
             val.push(style(OUTSIDE_ACROSS.to_string()));
         }
         val.push(style(BOTTOM_RIGHT.to_string()));
@@ -74,3 +375,75 @@ impl Grid {
         val
     }
 }
+
+impl<const BW: usize, const BH: usize, const N: usize> MultiGrid<BW, BH, N> {
+    /// Style the whole puzzle, with a border around each box of every grid
+    /// and blanks where there are no spaces.
+    pub fn to_styled(&self) -> Vec<StyledContent<String>> {
+        let (width, height) = self.size();
+        let (across, down) = (width / BW, height / BH);
+        // Whether there's a box one to the left of and one above (x, y),
+        // counting in boxes, so that 0 is off the edge.
+        let used =
+            |x: usize, y: usize| x > 0 && y > 0 && self.get((x - 1) * BW, (y - 1) * BH).is_some();
+        let mut val = vec![];
+
+        for y in 0..=down {
+            for x in 0..=across {
+                let arms = [
+                    used(x, y) || used(x + 1, y),
+                    used(x, y + 1) || used(x + 1, y + 1),
+                    used(x, y) || used(x, y + 1),
+                    used(x + 1, y) || used(x + 1, y + 1),
+                ];
+                val.push(style(FRAME_BORDERS.junction(arms).to_string()));
+                if x < across {
+                    let edge = if arms[3] { FRAME_BORDERS.across } else { ' ' };
+                    val.push(style(edge.to_string().repeat(BW * 3)));
+                }
+            }
+            val.push(style("\n".to_string()));
+            if y == down {
+                break;
+            }
+
+            for row in y * BH..(y + 1) * BH {
+                for x in 0..=across {
+                    let edge = if used(x, y + 1) || used(x + 1, y + 1) {
+                        FRAME_BORDERS.down
+                    } else {
+                        ' '
+                    };
+                    val.push(style(edge.to_string()));
+                    if x == across {
+                        break;
+                    }
+                    for column in x * BW..(x + 1) * BW {
+                        val.push(match self.get(column, row) {
+                            Some(Space::Occupied(v)) => style(format!(" {} ", v))
+                                .with(v.color())
+                                .attribute(Attribute::Bold),
+                            Some(Space::Empty) => style(" . ".to_string()).with(Color::Grey),
+                            None => style("   ".to_string()),
+                        });
+                    }
+                }
+                val.push(style("\n".to_string()));
+            }
+        }
+
+        val
+    }
+}
+
+#[cfg(test)]
+impl<const BW: usize, const BH: usize, const N: usize> MultiGrid<BW, BH, N> {
+    /// The text of `to_styled` without its colours, for tests to compare
+    /// lines of, as a grid's `to_string` is for a single grid.
+    pub(super) fn to_text(&self) -> String {
+        self.to_styled()
+            .iter()
+            .map(|s| s.content().as_str())
+            .collect()
+    }
+}
@@ -0,0 +1,97 @@
+use std::ops::Not;
+
+use crate::space::Space;
+
+use super::{Board, Constraint};
+
+impl<const BW: usize, const BH: usize, const N: usize> Board<BW, BH, N> {
+    /// Explain why this grid has no solution, as a minimal set of givens
+    /// that already contradict each other: removing any one of them leaves
+    /// the rest solvable. Returns `None` if the grid can be solved.
+    pub fn unsat_core(&self) -> Option<Vec<(u8, u8)>> {
+        if self.count_solutions(1) > 0 {
+            return None;
+        }
+
+        // Two equal givens in a unit are the most common mistake, and need no
+        // search to find.
+        for unit in self.layout.units.iter().map(Constraint::spaces) {
+            for (n, &a) in unit.iter().enumerate() {
+                for &b in unit[n + 1..].iter() {
+                    if self.spaces[a].is_occupied() && self.spaces[a] == self.spaces[b] {
+                        return Some(vec![Self::position(a), Self::position(b)]);
+                    }
+                }
+            }
+        }
+
+        // Otherwise drop each given in turn, keeping it only if the rest
+        // become solvable without it.
+        let mut core = self.clone();
+        for i in 0..N * N {
+            let given = core.spaces[i];
+            if given.is_occupied().not() {
+                continue;
+            }
+            core.spaces[i] = Space::Empty;
+            if core.count_solutions(1) > 0 {
+                core.spaces[i] = given;
+            }
+        }
+
+        Some(core.occupied_positions())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{grid::Grid, value::Value};
+
+    #[test]
+    fn solvable_grid_has_no_core() {
+        assert_eq!(Grid::empty().unsat_core(), None);
+    }
+
+    #[test]
+    fn duplicate_givens_are_the_core() {
+        let grid: Grid =
+            "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.33."
+                .parse()
+                .unwrap();
+
+        assert_eq!(grid.unsat_core(), Some(vec![(6, 8), (7, 8)]));
+    }
+
+    #[test]
+    fn core_found_by_search() {
+        // No unit repeats a value, but the top-left space has nowhere to go:
+        // 1-3 are in its row, 4-6 in its column and 7-9 in its square.
+        let mut grid = Grid::empty();
+        let givens = [
+            (3, 0),
+            (4, 0),
+            (5, 0),
+            (0, 3),
+            (0, 4),
+            (0, 5),
+            (1, 1),
+            (2, 2),
+            (1, 2),
+        ];
+        for (n, &(x, y)) in givens.iter().enumerate() {
+            grid.set(x, y, Value::from(n as u8 + 1));
+        }
+        grid.set(8, 8, Value::from(1));
+
+        let mut core = grid.unsat_core().unwrap();
+        core.sort();
+        let mut expected = givens.to_vec();
+        expected.sort();
+        assert_eq!(core, expected);
+        for &(x, y) in core.iter() {
+            let mut relaxed = grid.clone();
+            relaxed.set_empty(x, y);
+            assert!(relaxed.count_solutions(1) > 0);
+        }
+    }
+}
@@ -1,5 +1,18 @@
+mod candidates;
 mod grid;
+mod solver;
 mod space;
+mod technique;
 mod value;
 
-pub use grid::Grid;
+pub use candidates::Candidates;
+pub use grid::{
+    Backbone, Board, Constraint, GaveUp, Generated, Grid, Grid16, Grid25, Grid4, Grid6, Grid8,
+    Hint, LogicalSolve, MultiGrid, ParseGridError, Propagate,
+};
+pub use solver::{
+    Backtracking, Propagation, SolveOptions, SolveReport, Solver, Solvers, Stochastic, Stop,
+};
+pub use space::Space;
+pub use technique::{Technique, UnknownTechnique};
+pub use value::Value;
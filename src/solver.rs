@@ -0,0 +1,319 @@
+use std::{
+    fmt,
+    ops::Not,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::grid::Board;
+
+/// Limits on how much work a solver may do before giving up.
+#[derive(Clone, Debug, Default)]
+pub struct SolveOptions {
+    /// Stop after visiting this many nodes of the search.
+    pub node_limit: Option<u64>,
+    /// Stop once this much time has passed.
+    pub time_limit: Option<Duration>,
+    /// Stop as soon as this is set, e.g. from another thread.
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+/// Why a solver gave up before finishing its search.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Stop {
+    NodeLimit,
+    TimeLimit,
+    Cancelled,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Self::NodeLimit => "node limit reached",
+            Self::TimeLimit => "time limit reached",
+            Self::Cancelled => "cancelled",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// What a solver found, and how much work it took to find it.
+#[derive(Clone, Debug)]
+pub struct SolveReport<const BW: usize = 3, const BH: usize = 3, const N: usize = 9> {
+    pub solution: Option<Board<BW, BH, N>>,
+    /// Set if the search was cut short, in which case a missing solution
+    /// doesn't mean the grid is unsolvable.
+    pub stopped: Option<Stop>,
+    /// Search states visited.
+    pub nodes: u64,
+    /// Values undone after they led to a dead end.
+    pub backtracks: u64,
+    /// Values tried where there was more than one to choose from.
+    pub guesses: u64,
+    pub elapsed: Duration,
+}
+
+/// Tracks the work done by a search, and whether it should stop.
+pub(crate) struct Search<'a> {
+    options: &'a SolveOptions,
+    started: Instant,
+    stopped: Option<Stop>,
+    pub(crate) nodes: u64,
+    pub(crate) backtracks: u64,
+    pub(crate) guesses: u64,
+}
+
+impl<'a> Search<'a> {
+    pub(crate) fn new(options: &'a SolveOptions) -> Self {
+        Self {
+            options,
+            started: Instant::now(),
+            stopped: None,
+            nodes: 0,
+            backtracks: 0,
+            guesses: 0,
+        }
+    }
+
+    /// Count a visit to a new node, returning whether the search may go on.
+    pub(crate) fn visit(&mut self) -> bool {
+        self.nodes += 1;
+
+        let options = self.options;
+        if let Some(limit) = options.node_limit {
+            if self.nodes > limit {
+                self.stopped = Some(Stop::NodeLimit);
+            }
+        }
+        if let Some(limit) = options.time_limit {
+            if self.started.elapsed() > limit {
+                self.stopped = Some(Stop::TimeLimit);
+            }
+        }
+        if let Some(cancel) = &options.cancel {
+            if cancel.load(Ordering::Relaxed) {
+                self.stopped = Some(Stop::Cancelled);
+            }
+        }
+
+        self.stopped.is_none()
+    }
+
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stopped.is_some()
+    }
+
+    pub(crate) fn report<const BW: usize, const BH: usize, const N: usize>(
+        self,
+        solution: Option<Board<BW, BH, N>>,
+    ) -> SolveReport<BW, BH, N> {
+        SolveReport {
+            solution,
+            stopped: self.stopped,
+            nodes: self.nodes,
+            backtracks: self.backtracks,
+            guesses: self.guesses,
+            elapsed: self.started.elapsed(),
+        }
+    }
+}
+
+/// A strategy for solving sudoku, on grids with boxes `BW` by `BH`.
+pub trait Solver<const BW: usize = 3, const BH: usize = 3, const N: usize = 9>:
+    Send + Sync
+{
+    /// The name this solver is selected by, e.g. with `suds solve --strategy`.
+    fn name(&self) -> &str;
+
+    fn solve_with(&self, grid: Board<BW, BH, N>, options: &SolveOptions) -> SolveReport<BW, BH, N>;
+
+    fn solve(&self, grid: Board<BW, BH, N>) -> Option<Board<BW, BH, N>> {
+        self.solve_with(grid, &SolveOptions::default()).solution
+    }
+}
+
+/// Depth-first search, filling spaces in row-major order.
+pub struct Backtracking;
+
+impl<const BW: usize, const BH: usize, const N: usize> Solver<BW, BH, N> for Backtracking {
+    fn name(&self) -> &str {
+        "backtracking"
+    }
+
+    fn solve_with(&self, grid: Board<BW, BH, N>, options: &SolveOptions) -> SolveReport<BW, BH, N> {
+        grid.backtracking_solve_with(options)
+    }
+}
+
+/// Fill the empty spaces at random until the grid happens to be solved.
+pub struct Stochastic;
+
+impl<const BW: usize, const BH: usize, const N: usize> Solver<BW, BH, N> for Stochastic {
+    fn name(&self) -> &str {
+        "stochastic"
+    }
+
+    fn solve_with(&self, grid: Board<BW, BH, N>, options: &SolveOptions) -> SolveReport<BW, BH, N> {
+        grid.stochastic_solve_with(options)
+    }
+}
+
+/// Constraint propagation, guessing only when propagation stalls.
+pub struct Propagation;
+
+impl<const BW: usize, const BH: usize, const N: usize> Solver<BW, BH, N> for Propagation {
+    fn name(&self) -> &str {
+        "propagation"
+    }
+
+    fn solve_with(&self, grid: Board<BW, BH, N>, options: &SolveOptions) -> SolveReport<BW, BH, N> {
+        grid.propagation_solve_with(options)
+    }
+}
+
+/// The solvers available to choose from by name.
+pub struct Solvers<const BW: usize = 3, const BH: usize = 3, const N: usize = 9> {
+    solvers: Vec<Box<dyn Solver<BW, BH, N>>>,
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Solvers<BW, BH, N> {
+    /// A registry with no solvers in it at all.
+    pub fn new() -> Self {
+        Self { solvers: vec![] }
+    }
+
+    /// Add `solver`, replacing any registered solver with the same name,
+    /// ignoring case as `get` does.
+    pub fn register<S: Solver<BW, BH, N> + 'static>(&mut self, solver: S) {
+        self.solvers
+            .retain(|s| s.name().eq_ignore_ascii_case(solver.name()).not());
+        self.solvers.push(Box::new(solver));
+    }
+
+    /// Look up a solver by name, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&dyn Solver<BW, BH, N>> {
+        self.iter().find(|s| s.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Solver<BW, BH, N>> {
+        self.solvers.iter().map(|s| s.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.iter().map(|s| s.name()).collect()
+    }
+}
+
+impl<const BW: usize, const BH: usize, const N: usize> Default for Solvers<BW, BH, N> {
+    /// A registry with every built-in solver in it.
+    fn default() -> Self {
+        let mut solvers = Self::new();
+        solvers.register(Backtracking);
+        solvers.register(Stochastic);
+        solvers.register(Propagation);
+
+        solvers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+
+    use super::*;
+
+    struct Unsolvable;
+
+    impl Solver for Unsolvable {
+        fn name(&self) -> &str {
+            "unsolvable"
+        }
+
+        fn solve_with(&self, _: Grid, options: &SolveOptions) -> SolveReport {
+            Search::new(options).report(None)
+        }
+    }
+
+    #[test]
+    fn register_custom_solver() {
+        let mut solvers = Solvers::default();
+        solvers.register(Unsolvable);
+
+        assert_eq!(
+            solvers.names(),
+            vec!["backtracking", "stochastic", "propagation", "unsolvable"]
+        );
+        let solver = solvers.get("Unsolvable").unwrap();
+        assert_eq!(solver.solve(Grid::empty()), None);
+    }
+
+    #[test]
+    fn node_limit_stops_search() {
+        let options = SolveOptions {
+            node_limit: Some(10),
+            ..SolveOptions::default()
+        };
+        let report = Backtracking.solve_with(Grid::empty(), &options);
+
+        assert_eq!(report.solution, None);
+        assert_eq!(report.stopped, Some(Stop::NodeLimit));
+        assert_eq!(report.nodes, 11);
+    }
+
+    #[test]
+    fn cancelled_search_stops() {
+        let cancel = Arc::new(AtomicBool::new(true));
+        let options = SolveOptions {
+            cancel: Some(cancel),
+            ..SolveOptions::default()
+        };
+        let report = Stochastic.solve_with(Grid::empty(), &options);
+
+        assert_eq!(report.solution, None);
+        assert_eq!(report.stopped, Some(Stop::Cancelled));
+    }
+
+    #[test]
+    fn report_counts_work() {
+        let report = Backtracking.solve_with(Grid::empty(), &SolveOptions::default());
+
+        assert!(report.solution.unwrap().is_solved());
+        assert_eq!(report.stopped, None);
+        assert!(report.nodes >= 82);
+        assert!(report.guesses > 0);
+    }
+
+    #[test]
+    fn register_replaces_by_name() {
+        let mut solvers: Solvers = Solvers::new();
+        solvers.register(Backtracking);
+        solvers.register(Backtracking);
+
+        assert_eq!(solvers.names(), vec!["backtracking"]);
+    }
+
+    #[test]
+    fn register_replaces_by_name_ignoring_case() {
+        struct Shouting;
+
+        impl Solver for Shouting {
+            fn name(&self) -> &str {
+                "BACKTRACKING"
+            }
+
+            fn solve_with(&self, grid: Grid, options: &SolveOptions) -> SolveReport {
+                Backtracking.solve_with(grid, options)
+            }
+        }
+
+        let mut solvers: Solvers = Solvers::new();
+        solvers.register(Backtracking);
+        solvers.register(Shouting);
+
+        assert_eq!(solvers.names(), vec!["BACKTRACKING"]);
+        assert_eq!(solvers.get("backtracking").unwrap().name(), "BACKTRACKING");
+    }
+}
@@ -15,6 +15,14 @@ impl Space {
             _ => true,
         }
     }
+
+    /// The space a symbol stands for, where `.` and `0` are empty.
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            '.' | '0' => Some(Self::Empty),
+            _ => Value::from_symbol(symbol).map(Self::Occupied),
+        }
+    }
 }
 
 impl fmt::Display for Space {
@@ -0,0 +1,98 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+/// A human-style solving technique, ordered from easiest to hardest.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, EnumIter)]
+pub enum Technique {
+    NakedSingle,
+    HiddenSingle,
+    LockedCandidates,
+    NakedPair,
+    HiddenPair,
+    NakedTriple,
+    HiddenTriple,
+    NakedQuad,
+    HiddenQuad,
+    XWing,
+    YWing,
+    Swordfish,
+}
+
+impl Technique {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::NakedSingle => "naked-single",
+            Self::HiddenSingle => "hidden-single",
+            Self::LockedCandidates => "locked-candidates",
+            Self::NakedPair => "naked-pair",
+            Self::HiddenPair => "hidden-pair",
+            Self::NakedTriple => "naked-triple",
+            Self::HiddenTriple => "hidden-triple",
+            Self::NakedQuad => "naked-quad",
+            Self::HiddenQuad => "hidden-quad",
+            Self::XWing => "xwing",
+            Self::YWing => "ywing",
+            Self::Swordfish => "swordfish",
+        }
+    }
+}
+
+impl fmt::Display for Technique {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownTechnique(String);
+
+impl fmt::Display for UnknownTechnique {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = Technique::iter().map(Technique::name).collect::<Vec<_>>();
+        write!(
+            f,
+            "unknown technique {:?}, expected one of: {}",
+            self.0,
+            names.join(", ")
+        )
+    }
+}
+
+impl Error for UnknownTechnique {}
+
+/// Lowercase and drop separators, so "X-Wing", "xwing" and "hidden triple"
+/// all match the names above.
+fn normalise(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+impl FromStr for Technique {
+    type Err = UnknownTechnique;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wanted = normalise(s);
+        Self::iter()
+            .find(|t| normalise(t.name()) == wanted)
+            .ok_or_else(|| UnknownTechnique(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("xwing", Some(Technique::XWing))]
+    #[test_case("X-Wing", Some(Technique::XWing))]
+    #[test_case("hidden triple", Some(Technique::HiddenTriple))]
+    #[test_case("guess", None)]
+    fn technique_names(name: &str, expected: Option<Technique>) {
+        assert_eq!(name.parse::<Technique>().ok(), expected);
+    }
+}
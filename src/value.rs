@@ -1,105 +1,120 @@
-use std::{collections::HashSet, fmt};
+use std::{fmt, ops::Not};
 
 use crossterm::style::Color;
-use lazy_static::lazy_static;
-use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
 
-lazy_static! {
-    pub static ref ALL_VALUES: HashSet<Value> = Value::iter().collect();
-}
-
-#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, EnumIter)]
-pub enum Value {
-    One,
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Nine,
-}
+/// One of the symbols a space can hold, numbered from 1. Grids of up to 9
+/// values use the digits, and larger ones carry on with the letters, so a
+/// 16x16 grid uses `1`-`9` then `A`-`G`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct Value(u8);
 
 impl Value {
+    /// The most values a grid can have, for 25x25 grids.
+    pub const MAX: u8 = 25;
+
+    /// The first `size` values, in order.
+    pub fn all(size: usize) -> impl Iterator<Item = Self> {
+        (1..=size as u8).map(Self::from)
+    }
+
+    /// The value a symbol stands for, ignoring case.
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        let number = match symbol.to_ascii_uppercase() {
+            c @ '1'..='9' => c as u8 - b'0',
+            c @ 'A'..='P' => c as u8 - b'A' + 10,
+            _ => return None,
+        };
+
+        Some(Self(number))
+    }
+
+    pub fn symbol(self) -> char {
+        match self.0 {
+            n @ 1..=9 => (b'0' + n) as char,
+            n => (b'A' + n - 10) as char,
+        }
+    }
+
     pub fn color(self) -> Color {
-        match self {
-            Self::One => Color::Rgb { r: 255, g: 0, b: 0 },
-            Self::Two => Color::Rgb {
+        match self.0 {
+            1 => Color::Rgb { r: 255, g: 0, b: 0 },
+            2 => Color::Rgb {
                 r: 255,
                 g: 120,
                 b: 0,
             },
-            Self::Three => Color::Rgb {
+            3 => Color::Rgb {
                 r: 255,
                 g: 255,
                 b: 0,
             },
-            Self::Four => Color::Rgb {
+            4 => Color::Rgb {
                 r: 120,
                 g: 255,
                 b: 0,
             },
-            Self::Five => Color::Rgb {
+            5 => Color::Rgb {
                 r: 0,
                 g: 180,
                 b: 80,
             },
-            Self::Six => Color::Rgb {
+            6 => Color::Rgb {
                 r: 0,
                 g: 150,
                 b: 255,
             },
-            Self::Seven => Color::Rgb {
+            7 => Color::Rgb {
                 r: 60,
                 g: 80,
                 b: 220,
             },
-            Self::Eight => Color::Rgb {
+            8 => Color::Rgb {
                 r: 140,
                 g: 40,
                 b: 255,
             },
-            Self::Nine => Color::Rgb {
+            9 => Color::Rgb {
                 r: 240,
                 g: 20,
                 b: 255,
             },
+            // The letters get paler shades around the same colour wheel, so
+            // they can't be mistaken for the digits.
+            n => {
+                let step = u16::from(n - 10) * 360 / 16;
+                let channel = |offset: u16| {
+                    let angle = (step + offset) % 360;
+                    let distance = angle.min(360 - angle);
+                    (255 - distance.min(120) * 100 / 120) as u8
+                };
+                Color::Rgb {
+                    r: channel(0),
+                    g: channel(240),
+                    b: channel(120),
+                }
+            }
         }
     }
 }
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let val = match self {
-            Self::One => '1',
-            Self::Two => '2',
-            Self::Three => '3',
-            Self::Four => '4',
-            Self::Five => '5',
-            Self::Six => '6',
-            Self::Seven => '7',
-            Self::Eight => '8',
-            Self::Nine => '9',
-        };
-        write!(f, "{}", val)
+        write!(f, "{}", self.symbol())
     }
 }
 
 impl From<u8> for Value {
     fn from(number: u8) -> Self {
-        match number {
-            1 => Self::One,
-            2 => Self::Two,
-            3 => Self::Three,
-            4 => Self::Four,
-            5 => Self::Five,
-            6 => Self::Six,
-            7 => Self::Seven,
-            8 => Self::Eight,
-            9 => Self::Nine,
-            _ => panic!("value cannot be less than 1, or greater than 9"),
+        if (1..=Self::MAX).contains(&number).not() {
+            panic!("value cannot be less than 1, or greater than {}", Self::MAX);
         }
+
+        Self(number)
+    }
+}
+
+impl From<Value> for u8 {
+    fn from(value: Value) -> Self {
+        value.0
     }
 }